@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::{InspectorPlugin, WorldInspectorParams, WorldInspectorPlugin};
 use bevy_rapier3d::prelude::{NoUserData, RapierPhysicsPlugin};
-use bevy_ymir::{PlayerPositionChangedEvent, YmirPlugin};
+use bevy_ymir::{LoadingShape, TerrainObserver, YmirPlugin};
 use rustpg::{
     core::{camera::CameraPlugin, spectator::SpectatorPlugin},
     nycthemeron::{time_of_day::TimeOfDay, NycthemeronPlugin},
@@ -21,16 +21,21 @@ fn main() {
         .add_plugin(SpectatorPlugin)
         .add_plugin(NycthemeronPlugin {
             time_of_day: TimeOfDay::new(12.0, 0.0, 0.0, 0.0),
-            inspectors:false,
+            inspectors: false,
         })
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
         .add_plugin(YmirPlugin {
-            chunk_distance: 5,
             object_distance: 1,
+            physics_distance: 1,
+            ground_cover_distance: 1,
+            max_chunk_tasks: 8,
+            chunk_uploads_per_frame: 2,
+            noise_cache_size: 512,
+            disk_cache: Some("chunk_cache".into()),
             inspectors,
             ..default()
         })
-        .add_system(send_player_pos_events);
+        .add_system(observe_cameras);
 
     if inspectors {
         app.add_plugin(InspectorPlugin::<AmbientLight>::default())
@@ -45,11 +50,11 @@ fn main() {
     app.run();
 }
 
-fn send_player_pos_events(
-    query: Query<&Transform, With<Camera>>,
-    mut events: EventWriter<PlayerPositionChangedEvent>,
-) {
-    if let Ok(transform) = query.get_single() {
-        events.send(PlayerPositionChangedEvent(transform.translation));
+fn observe_cameras(mut commands: Commands, query: Query<Entity, Added<Camera>>) {
+    for camera in &query {
+        commands.entity(camera).insert(TerrainObserver {
+            chunk_distance: 5,
+            shape: LoadingShape::Circle,
+        });
     }
 }
@@ -0,0 +1,111 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_inspector_egui::{bevy_egui::EguiContext, Inspectable};
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    edit::{Brush, TerrainEditEvent},
+    physics::ChunkCollider,
+};
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub enum BrushMode {
+    #[default]
+    Raise,
+    Lower,
+    Smooth,
+    /// Flattens towards the height where the stroke started.
+    Flatten,
+    /// Paints `BrushTool::color` over the terrain colours.
+    Paint,
+}
+
+/// Sculpts or paints the terrain under the cursor while the left mouse button is held. Only chunks
+/// within `YmirPlugin::physics_distance` have colliders to hit.
+#[derive(Resource, Clone, Inspectable)]
+pub struct BrushTool {
+    pub enabled: bool,
+    pub mode: BrushMode,
+    #[inspectable(min = 0.0)]
+    pub radius: f32,
+    /// World units per second for `Raise` and `Lower`, blend per second otherwise.
+    #[inspectable(min = 0.0)]
+    pub strength: f32,
+    pub color: Color,
+}
+
+impl Default for BrushTool {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: default(),
+            radius: 24.0,
+            strength: 20.0,
+            color: Color::rgb(0.45, 0.33, 0.22),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn use_brush(
+    brush: Res<BrushTool>,
+    windows: Res<Windows>,
+    buttons: Res<Input<MouseButton>>,
+    time: Res<Time>,
+    rapier_context: Option<Res<RapierContext>>,
+    egui_context: Option<ResMut<EguiContext>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    chunk_colliders: Query<&ChunkCollider>,
+    mut flatten_height: Local<Option<f32>>,
+    mut events: EventWriter<TerrainEditEvent>,
+) {
+    let Some(rapier_context) = rapier_context else {
+        return;
+    };
+    if !brush.enabled || !buttons.pressed(MouseButton::Left) {
+        *flatten_height = None;
+        return;
+    }
+    // NOTE clicks on the inspector windows should not sculpt the terrain behind them.
+    if let Some(mut egui_context) = egui_context {
+        if egui_context.ctx_mut().wants_pointer_input() {
+            return;
+        }
+    }
+
+    let Some(cursor) = windows
+        .get_primary()
+        .and_then(|window| window.cursor_position())
+    else {
+        return;
+    };
+    let Some(ray) = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .find_map(|(camera, transform)| camera.viewport_to_world(transform, cursor))
+    else {
+        return;
+    };
+
+    let terrain: HashSet<Entity> = chunk_colliders.iter().map(|collider| collider.0).collect();
+    let is_terrain = |entity| terrain.contains(&entity);
+    let filter = QueryFilter::new().predicate(&is_terrain);
+    let Some((_, toi)) = rapier_context.cast_ray(ray.origin, ray.direction, f32::MAX, true, filter)
+    else {
+        return;
+    };
+    let center = ray.origin + ray.direction * toi;
+
+    let brush_mode = match brush.mode {
+        BrushMode::Raise => Brush::Raise,
+        BrushMode::Lower => Brush::Lower,
+        BrushMode::Smooth => Brush::Smooth,
+        BrushMode::Flatten => Brush::Flatten(*flatten_height.get_or_insert(center.y)),
+        BrushMode::Paint => Brush::Paint(brush.color),
+    };
+    events.send(TerrainEditEvent {
+        brush: brush_mode,
+        center,
+        radius: brush.radius,
+        strength: brush.strength * time.delta_seconds(),
+    });
+}
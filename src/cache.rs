@@ -0,0 +1,353 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use bevy::{prelude::*, tasks::IoTaskPool, utils::HashMap};
+
+use super::noise::{NoiseConfig, NoiseMap};
+
+/// Hash of every setting the `NoiseMap` of a chunk depends on.
+pub(crate) fn noise_key(noise_config: &NoiseConfig, grid_size: usize) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(&noise_config.seed.to_le_bytes());
+    hasher.write(&(noise_config.octaves as u64).to_le_bytes());
+    hasher.write(&noise_config.frequency.to_le_bytes());
+    hasher.write(&noise_config.lacunarity.to_le_bytes());
+    hasher.write(&noise_config.persistence.to_le_bytes());
+    hasher.write(&noise_config.offset.x.to_le_bytes());
+    hasher.write(&noise_config.offset.y.to_le_bytes());
+    hasher.write(&[noise_config.falloff as u8]);
+    hasher.write(&(grid_size as u64).to_le_bytes());
+    hasher.finish()
+}
+
+/// FNV-1a over explicitly encoded bytes. Unlike `DefaultHasher`, its output stays the same
+/// across platforms and Rust releases, so it can name files on disk.
+pub(crate) struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StableHasher {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Least recently used chunk noise, keyed by chunk and [`noise_key`], so chunks re-entering
+/// the pool or a config set back to an earlier value skip sampling the noise.
+#[derive(Resource)]
+pub(crate) struct NoiseCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<NoiseCacheKey, (NoiseMap, u64)>,
+    /// Keys by the tick they were last used at, so the oldest is found without a scan.
+    recency: BTreeMap<u64, NoiseCacheKey>,
+}
+
+type NoiseCacheKey = ((i32, i32), u64);
+
+impl NoiseCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::default(),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, chunk: (i32, i32), key: u64) -> Option<NoiseMap> {
+        let (map, last_used) = self.entries.get_mut(&(chunk, key))?;
+        self.tick += 1;
+        self.recency.remove(last_used);
+        self.recency.insert(self.tick, (chunk, key));
+        *last_used = self.tick;
+        Some(map.clone())
+    }
+
+    pub fn insert(&mut self, chunk: (i32, i32), key: u64, map: NoiseMap) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert((chunk, key), (map, self.tick)) {
+            self.recency.remove(&last_used);
+        }
+        self.recency.insert(self.tick, (chunk, key));
+
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// Bumped whenever the layout of chunk files changes, so older files are regenerated.
+const CHUNK_FILE_VERSION: u32 = 1;
+const CHUNK_FILE_MAGIC: &[u8; 4] = b"YMIR";
+const CHUNK_FILE_HEADER: usize = 20;
+
+/// Directory inside the asset folder chunk noise is persisted to between launches.
+#[derive(Resource)]
+pub(crate) struct DiskCache(pub Option<String>);
+
+impl DiskCache {
+    /// File of a chunk relative to the asset folder, grouped by seed and [`noise_key`].
+    pub fn chunk_path(&self, seed: u32, key: u64, (x, y): (i32, i32)) -> Option<PathBuf> {
+        let dir = self.0.as_ref()?;
+        Some(
+            Path::new(dir)
+                .join(seed.to_string())
+                .join(format!("{key:016x}"))
+                .join(format!("{x}_{y}.chunk")),
+        )
+    }
+}
+
+/// Noise of a chunk read from the disk cache through the asset IO, generating and writing it
+/// back when the file is missing, outdated or corrupt.
+pub(crate) async fn load_or_generate(
+    asset_server: &AssetServer,
+    path: &Path,
+    key: u64,
+    grid_size: usize,
+    generate: impl FnOnce() -> NoiseMap,
+) -> NoiseMap {
+    if let Ok(bytes) = asset_server.asset_io().load_path(path).await {
+        if let Some(map) = decode(&bytes, key, grid_size) {
+            return map;
+        }
+    }
+
+    let map = generate();
+    save(asset_server, path, encode(&map, key));
+    map
+}
+
+#[cfg(all(not(target_arch = "wasm32"), not(target_os = "android")))]
+fn save(asset_server: &AssetServer, path: &Path, bytes: Vec<u8>) {
+    use bevy::asset::FileAssetIo;
+
+    // NOTE Bevy's asset IO can only read, so files are written next to the assets it reads,
+    // on the IO pool to keep blocking writes off the chunk tasks.
+    let Some(io) = asset_server.asset_io().downcast_ref::<FileAssetIo>() else {
+        return;
+    };
+    let path = io.root_path().join(path);
+    IoTaskPool::get()
+        .spawn(async move {
+            let written = path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&path, bytes));
+            if let Err(err) = written {
+                warn!("could not write chunk cache {}: {err}", path.display());
+            }
+        })
+        .detach();
+}
+
+#[cfg(any(target_arch = "wasm32", target_os = "android"))]
+fn save(_asset_server: &AssetServer, _path: &Path, _bytes: Vec<u8>) {}
+
+/// Magic, version, key and grid size, followed by the noise values as little-endian `f32`s.
+fn encode(map: &NoiseMap, key: u64) -> Vec<u8> {
+    let (size, _) = map.size();
+    let mut bytes = Vec::with_capacity(CHUNK_FILE_HEADER + size * size * 4);
+    bytes.extend_from_slice(CHUNK_FILE_MAGIC);
+    bytes.extend_from_slice(&CHUNK_FILE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&key.to_le_bytes());
+    bytes.extend_from_slice(&(size as u32).to_le_bytes());
+    for value in map.values() {
+        bytes.extend_from_slice(&(value as f32).to_le_bytes());
+    }
+    bytes
+}
+
+/// Noise of a chunk file, or `None` if it is not a valid file for `key` and `grid_size`.
+fn decode(bytes: &[u8], key: u64, grid_size: usize) -> Option<NoiseMap> {
+    let (header, body) = bytes.split_at(bytes.len().min(CHUNK_FILE_HEADER));
+    if header.len() < CHUNK_FILE_HEADER || &header[0..4] != CHUNK_FILE_MAGIC {
+        return None;
+    }
+    let version = u32::from_le_bytes(header[4..8].try_into().ok()?);
+    let file_key = u64::from_le_bytes(header[8..16].try_into().ok()?);
+    let size = u32::from_le_bytes(header[16..20].try_into().ok()?) as usize;
+    // NOTE the size is checked too, as meshes can't be built from a grid of the wrong size.
+    if version != CHUNK_FILE_VERSION || file_key != key || size != grid_size {
+        return None;
+    }
+    if Some(body.len()) != size.checked_mul(size)?.checked_mul(4) {
+        return None;
+    }
+
+    let values = body
+        .chunks_exact(4)
+        .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]) as f64)
+        .collect();
+    Some(NoiseMap::from_values(size, values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: u64 = 0x0123_4567_89ab_cdef;
+
+    fn map(value: f64) -> NoiseMap {
+        NoiseMap::from_values(2, vec![value; 4])
+    }
+
+    fn cached(cache: &NoiseCache, chunk: (i32, i32)) -> bool {
+        cache.entries.contains_key(&(chunk, 1))
+    }
+
+    #[test]
+    fn evicts_least_recently_inserted() {
+        let mut cache = NoiseCache::new(2);
+        cache.insert((0, 0), 1, map(0.0));
+        cache.insert((1, 0), 1, map(1.0));
+        cache.insert((2, 0), 1, map(2.0));
+
+        assert!(!cached(&cache, (0, 0)));
+        assert!(cached(&cache, (1, 0)));
+        assert!(cached(&cache, (2, 0)));
+    }
+
+    #[test]
+    fn get_keeps_entry_from_eviction() {
+        let mut cache = NoiseCache::new(2);
+        cache.insert((0, 0), 1, map(0.0));
+        cache.insert((1, 0), 1, map(1.0));
+        assert!(cache.get((0, 0), 1).is_some());
+        cache.insert((2, 0), 1, map(2.0));
+
+        assert!(cached(&cache, (0, 0)));
+        assert!(!cached(&cache, (1, 0)));
+        assert!(cached(&cache, (2, 0)));
+    }
+
+    #[test]
+    fn reinserting_replaces_entry() {
+        let mut cache = NoiseCache::new(2);
+        cache.insert((0, 0), 1, map(0.0));
+        cache.insert((0, 0), 1, map(1.0));
+        cache.insert((1, 0), 1, map(2.0));
+
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.recency.len(), 2);
+        assert_eq!(cache.get((0, 0), 1).unwrap().get_value(0, 0), 1.0);
+    }
+
+    #[test]
+    fn keys_are_separate_entries() {
+        let mut cache = NoiseCache::new(4);
+        cache.insert((0, 0), 1, map(0.0));
+        cache.insert((0, 0), 2, map(1.0));
+
+        assert_eq!(cache.get((0, 0), 1).unwrap().get_value(0, 0), 0.0);
+        assert_eq!(cache.get((0, 0), 2).unwrap().get_value(0, 0), 1.0);
+        assert!(cache.get((0, 0), 3).is_none());
+    }
+
+    #[test]
+    fn zero_capacity_stores_nothing() {
+        let mut cache = NoiseCache::new(0);
+        cache.insert((0, 0), 1, map(0.0));
+
+        assert!(cache.get((0, 0), 1).is_none());
+    }
+
+    #[test]
+    fn noise_key_is_stable() {
+        // NOTE pinned, as changing it orphans every chunk cache on disk.
+        assert_eq!(
+            noise_key(&NoiseConfig::default(), 65),
+            0xf9ae_2d33_c397_b30b
+        );
+    }
+
+    #[test]
+    fn noise_key_depends_on_config() {
+        let config = NoiseConfig::default();
+        let reseeded = NoiseConfig {
+            seed: 1,
+            ..default()
+        };
+
+        assert_ne!(noise_key(&config, 65), noise_key(&reseeded, 65));
+        assert_ne!(noise_key(&config, 65), noise_key(&config, 33));
+    }
+
+    fn chunk(size: usize) -> NoiseMap {
+        let values = (0..size * size).map(|i| i as f64 / 8.0 - 1.0).collect();
+        NoiseMap::from_values(size, values)
+    }
+
+    fn header(version: u32, key: u64, size: u32) -> Vec<u8> {
+        let mut bytes = CHUNK_FILE_MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&key.to_le_bytes());
+        bytes.extend_from_slice(&size.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn round_trips() {
+        let map = chunk(5);
+        let decoded = decode(&encode(&map, KEY), KEY, 5).expect("valid chunk file");
+
+        assert_eq!(decoded.size(), map.size());
+        assert_eq!(decoded.values(), map.values());
+    }
+
+    #[test]
+    fn rejects_other_key_version_or_magic() {
+        let bytes = encode(&chunk(5), KEY);
+        assert!(decode(&bytes, KEY + 1, 5).is_none());
+
+        let mut outdated = bytes.clone();
+        outdated[4..8].copy_from_slice(&(CHUNK_FILE_VERSION + 1).to_le_bytes());
+        assert!(decode(&outdated, KEY, 5).is_none());
+
+        let mut foreign = bytes;
+        foreign[0..4].copy_from_slice(b"NOPE");
+        assert!(decode(&foreign, KEY, 5).is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = encode(&chunk(5), KEY);
+
+        assert!(decode(&bytes[..bytes.len() - 1], KEY, 5).is_none());
+        assert!(decode(&bytes[..CHUNK_FILE_HEADER], KEY, 5).is_none());
+        assert!(decode(&bytes[..CHUNK_FILE_HEADER - 1], KEY, 5).is_none());
+        assert!(decode(&[], KEY, 5).is_none());
+    }
+
+    #[test]
+    fn rejects_other_grid_sizes() {
+        assert!(decode(&encode(&chunk(5), KEY), KEY, 9).is_none());
+        assert!(decode(&header(CHUNK_FILE_VERSION, KEY, 0), KEY, 5).is_none());
+    }
+
+    #[test]
+    fn rejects_overflowing_sizes() {
+        let size = u32::MAX;
+        assert!(decode(&header(CHUNK_FILE_VERSION, KEY, size), KEY, size as usize).is_none());
+    }
+}
@@ -0,0 +1,722 @@
+use bevy::{
+    prelude::*,
+    render::primitives::Aabb,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::{HashMap, HashSet},
+};
+use futures_lite::future;
+
+use super::{
+    density_config, density_noise,
+    mesh::{self, MeshConfig, MeshImageData, TextureMode},
+    noise::{DensityLayers, NoiseMap},
+    replace_texture, Chunk, ChunkPool, ComputeMeshImageData, SpawnedChunks,
+};
+
+/// Bumped whenever the layout of saved edits changes. Version 1 had no paint layers.
+const EDITS_VERSION: u32 = 2;
+const EDITS_MAGIC: &[u8; 4] = b"YMED";
+
+#[derive(Clone, Copy)]
+pub enum Brush {
+    Raise,
+    Lower,
+    /// Pulls the terrain towards the given world height.
+    Flatten(f32),
+    Smooth,
+    /// Paints over the terrain colours, see [`ChunkPaint`].
+    Paint(Color),
+}
+
+/// Edits the terrain around `center`, fading out towards `radius`. `strength` is in world
+/// units for `Raise` and `Lower`, and a blend factor in `0.0..=1.0` for the other brushes.
+pub struct TerrainEditEvent {
+    pub brush: Brush,
+    pub center: Vec3,
+    pub radius: f32,
+    pub strength: f32,
+}
+
+/// Height deltas of a chunk on top of its noise, laid out like its `NoiseMap`.
+#[derive(Clone)]
+pub struct ChunkEdits {
+    size: usize,
+    deltas: Vec<f64>,
+}
+
+impl ChunkEdits {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            deltas: vec![0.0; size * size],
+        }
+    }
+
+    /// Noise of the chunk with the edits applied, resampled if the grid size changed since.
+    pub fn apply(&self, map: &NoiseMap) -> NoiseMap {
+        if map.size().0 != self.size {
+            return self.resampled(map.size().0).apply(map);
+        }
+
+        let values = map
+            .values()
+            .iter()
+            .zip(&self.deltas)
+            .map(|(value, delta)| value + delta)
+            .collect();
+        NoiseMap::from_values(self.size, values)
+    }
+
+    fn resampled(&self, size: usize) -> Self {
+        Self {
+            size,
+            deltas: resample(&self.deltas, self.size, size, |a, b, t| a + (b - a) * t),
+        }
+    }
+}
+
+/// Colours painted over a chunk in `TextureMode::Color`, one straight RGBA texel per vertex
+/// where alpha is the paint coverage.
+#[derive(Clone)]
+pub struct ChunkPaint {
+    size: usize,
+    texels: Vec<[u8; 4]>,
+}
+
+impl ChunkPaint {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            texels: vec![[0; 4]; size * size],
+        }
+    }
+
+    /// Blends `color` over the texel at column `x` and row `y`.
+    fn paint(&mut self, x: usize, y: usize, color: Color, blend: f32) {
+        if blend <= 0.0 {
+            return;
+        }
+        let texel = &mut self.texels[y * self.size + x];
+        let below = texel[3] as f32 / 255.0 * (1.0 - blend);
+        let alpha = blend + below;
+        let paint = [color.r(), color.g(), color.b()];
+        for (channel, paint) in texel.iter_mut().zip(paint) {
+            let value = (paint * blend + *channel as f32 / 255.0 * below) / alpha;
+            *channel = (value * 255.0).round() as u8;
+        }
+        texel[3] = (alpha * 255.0).round() as u8;
+    }
+
+    /// Composites the paint over a chunk texture, resampled if the grid size changed since.
+    pub fn composite(&self, image: &mut Image) {
+        let size = image.texture_descriptor.size;
+        if size.width != size.height || image.data.len() != (size.width * size.height * 4) as usize
+        {
+            return;
+        }
+        if size.width as usize != self.size {
+            return self.resampled(size.width as usize).composite(image);
+        }
+
+        for (pixel, texel) in image.data.chunks_exact_mut(4).zip(&self.texels) {
+            let alpha = texel[3] as f32 / 255.0;
+            for (channel, paint) in pixel.iter_mut().zip(&texel[..3]) {
+                *channel = (*channel as f32 * (1.0 - alpha) + *paint as f32 * alpha).round() as u8;
+            }
+        }
+    }
+
+    fn resampled(&self, size: usize) -> Self {
+        let lerp = |a: [u8; 4], b: [u8; 4], t: f64| {
+            let mut texel = [0; 4];
+            for ((channel, a), b) in texel.iter_mut().zip(a).zip(b) {
+                *channel = (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+            }
+            texel
+        };
+        Self {
+            size,
+            texels: resample(&self.texels, self.size, size, lerp),
+        }
+    }
+}
+
+/// Bilinearly resamples a grid of `from` by `from` values to `to` by `to`, keeping the border
+/// values on the border so neighbouring chunks still line up.
+fn resample<T: Copy>(
+    values: &[T],
+    from: usize,
+    to: usize,
+    lerp: impl Fn(T, T, f64) -> T,
+) -> Vec<T> {
+    let step = (from - 1) as f64 / (to - 1) as f64;
+    let at = |x: usize, y: usize| values[y * from + x];
+
+    let mut resampled = Vec::with_capacity(to * to);
+    for y in 0..to {
+        for x in 0..to {
+            let (fx, fy) = (x as f64 * step, y as f64 * step);
+            let (x0, y0) = (fx as usize, fy as usize);
+            let (x1, y1) = ((x0 + 1).min(from - 1), (y0 + 1).min(from - 1));
+            let (tx, ty) = (fx - x0 as f64, fy - y0 as f64);
+            let top = lerp(at(x0, y0), at(x1, y0), tx);
+            let bottom = lerp(at(x0, y1), at(x1, y1), tx);
+            resampled.push(lerp(top, bottom, ty));
+        }
+    }
+    resampled
+}
+
+/// Sculpted height deltas and paint of every edited chunk, kept when chunks unload. Save them
+/// with [`TerrainEdits::to_bytes`] and restore them with [`TerrainEdits::load`].
+#[derive(Resource, Default)]
+pub struct TerrainEdits {
+    chunks: HashMap<(i32, i32), ChunkEdits>,
+    paint: HashMap<(i32, i32), ChunkPaint>,
+    /// Chunks whose edits changed since their mesh was last rebuilt.
+    dirty: HashSet<(i32, i32)>,
+}
+
+impl TerrainEdits {
+    pub fn get(&self, chunk: (i32, i32)) -> Option<&ChunkEdits> {
+        self.chunks.get(&chunk)
+    }
+
+    pub fn get_paint(&self, chunk: (i32, i32)) -> Option<&ChunkPaint> {
+        self.paint.get(&chunk)
+    }
+
+    /// Paint of a chunk for textures baked with `mesh_config`, which only colour mode shows.
+    pub fn paint_for(&self, chunk: (i32, i32), mesh_config: &MeshConfig) -> Option<ChunkPaint> {
+        match mesh_config.texture_mode {
+            TextureMode::Color => self.paint.get(&chunk).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Noise of a chunk with its edits applied, if it has any.
+    pub fn apply(&self, chunk: (i32, i32), map: &NoiseMap) -> NoiseMap {
+        match self.chunks.get(&chunk) {
+            Some(edits) => edits.apply(map),
+            None => map.clone(),
+        }
+    }
+
+    /// Rebuilds the chunk from its edits, even if they did not change.
+    pub(crate) fn mark_dirty(&mut self, chunk: (i32, i32)) {
+        self.dirty.insert(chunk);
+    }
+
+    pub fn clear(&mut self) {
+        self.dirty.extend(self.chunks.keys());
+        self.dirty.extend(self.paint.keys());
+        self.chunks.clear();
+        self.paint.clear();
+    }
+
+    /// Magic and version, followed by the coordinates, grid size and little-endian `f32` deltas
+    /// of every edited chunk, then the coordinates, grid size and RGBA texels of every painted
+    /// chunk.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(EDITS_MAGIC);
+        bytes.extend_from_slice(&EDITS_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.chunks.len() as u32).to_le_bytes());
+        for (&(x, y), edits) in &self.chunks {
+            bytes.extend_from_slice(&x.to_le_bytes());
+            bytes.extend_from_slice(&y.to_le_bytes());
+            bytes.extend_from_slice(&(edits.size as u32).to_le_bytes());
+            for delta in &edits.deltas {
+                bytes.extend_from_slice(&(*delta as f32).to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&(self.paint.len() as u32).to_le_bytes());
+        for (&(x, y), paint) in &self.paint {
+            bytes.extend_from_slice(&x.to_le_bytes());
+            bytes.extend_from_slice(&y.to_le_bytes());
+            bytes.extend_from_slice(&(paint.size as u32).to_le_bytes());
+            bytes.extend(paint.texels.iter().flatten());
+        }
+        bytes
+    }
+
+    /// Replaces all edits with ones saved by [`TerrainEdits::to_bytes`], leaving them untouched
+    /// if `bytes` are not valid.
+    pub fn load(&mut self, bytes: &[u8]) -> bool {
+        let Some((chunks, paint)) = read_edits(bytes) else {
+            return false;
+        };
+        self.clear();
+        self.dirty.extend(chunks.keys());
+        self.dirty.extend(paint.keys());
+        self.chunks = chunks;
+        self.paint = paint;
+        true
+    }
+
+    /// Resamples edits made at another grid size, so new edits can be added to them.
+    fn resample(&mut self, size: usize) {
+        for edits in self.chunks.values_mut() {
+            if edits.size != size {
+                *edits = edits.resampled(size);
+            }
+        }
+        for paint in self.paint.values_mut() {
+            if paint.size != size {
+                *paint = paint.resampled(size);
+            }
+        }
+    }
+
+    /// Adds `change` to the vertex at global grid coordinates `vertex`, in every chunk sharing it.
+    fn add(&mut self, vertex: (i32, i32), change: f64, size: usize) {
+        for (chunk, (x, y)) in grid_chunks(vertex, size) {
+            let edits = self
+                .chunks
+                .entry(chunk)
+                .or_insert_with(|| ChunkEdits::new(size));
+            edits.deltas[y * size + x] += change;
+            self.dirty.insert(chunk);
+        }
+    }
+
+    /// Paints the vertex at global grid coordinates `vertex`, in every chunk sharing it.
+    fn add_paint(&mut self, vertex: (i32, i32), color: Color, blend: f32, size: usize) {
+        for (chunk, (x, y)) in grid_chunks(vertex, size) {
+            let paint = self
+                .paint
+                .entry(chunk)
+                .or_insert_with(|| ChunkPaint::new(size));
+            paint.paint(x, y, color, blend);
+            self.dirty.insert(chunk);
+        }
+    }
+
+    /// Edited noise value at global grid coordinates `vertex`, read from any loaded chunk.
+    fn value(
+        &self,
+        noise: &HashMap<(i32, i32), &NoiseMap>,
+        vertex: (i32, i32),
+        size: usize,
+    ) -> Option<f64> {
+        grid_chunks(vertex, size).find_map(|(chunk, (x, y))| {
+            let map = noise.get(&chunk)?;
+            let delta = self
+                .chunks
+                .get(&chunk)
+                .map_or(0.0, |edits| edits.deltas[y * size + x]);
+            Some(map.get_value(x, y) as f64 + delta)
+        })
+    }
+}
+
+type ReadEdits = (
+    HashMap<(i32, i32), ChunkEdits>,
+    HashMap<(i32, i32), ChunkPaint>,
+);
+
+fn read_edits(mut bytes: &[u8]) -> Option<ReadEdits> {
+    if take(&mut bytes, 4)? != EDITS_MAGIC {
+        return None;
+    }
+    let version = read_u32(&mut bytes)?;
+    if version == 0 || version > EDITS_VERSION {
+        return None;
+    }
+
+    let count = read_u32(&mut bytes)?;
+    let mut chunks = HashMap::default();
+    for _ in 0..count {
+        let x = read_u32(&mut bytes)? as i32;
+        let y = read_u32(&mut bytes)? as i32;
+        let size = read_u32(&mut bytes)? as usize;
+        if size < 2 {
+            return None;
+        }
+        let deltas = take(&mut bytes, size.checked_mul(size)?.checked_mul(4)?)?
+            .chunks_exact(4)
+            .map(|delta| f32::from_le_bytes([delta[0], delta[1], delta[2], delta[3]]) as f64)
+            .collect();
+        chunks.insert((x, y), ChunkEdits { size, deltas });
+    }
+
+    let mut paint = HashMap::default();
+    let paint_count = if version >= 2 {
+        read_u32(&mut bytes)?
+    } else {
+        0
+    };
+    for _ in 0..paint_count {
+        let x = read_u32(&mut bytes)? as i32;
+        let y = read_u32(&mut bytes)? as i32;
+        let size = read_u32(&mut bytes)? as usize;
+        if size < 2 {
+            return None;
+        }
+        let texels = take(&mut bytes, size.checked_mul(size)?.checked_mul(4)?)?
+            .chunks_exact(4)
+            .map(|texel| [texel[0], texel[1], texel[2], texel[3]])
+            .collect();
+        paint.insert((x, y), ChunkPaint { size, texels });
+    }
+    Some((chunks, paint))
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Some(head)
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(take(bytes, 4)?.try_into().ok()?))
+}
+
+/// Chunks containing the vertex at global grid coordinates `vertex`, with its column and row
+/// in each. Vertices on a chunk border are shared with the neighbouring chunks.
+fn grid_chunks(
+    (x, y): (i32, i32),
+    size: usize,
+) -> impl Iterator<Item = ((i32, i32), (usize, usize))> {
+    let cells = size as i32 - 1;
+    let along = move |v: i32| {
+        let (chunk, local) = (v.div_euclid(cells), v.rem_euclid(cells));
+        let border = (local == 0).then_some((chunk - 1, cells as usize));
+        std::iter::once((chunk, local as usize)).chain(border)
+    };
+    along(x).flat_map(move |(chunk_x, local_x)| {
+        along(y).map(move |(chunk_y, local_y)| ((chunk_x, chunk_y), (local_x, local_y)))
+    })
+}
+
+pub(crate) fn apply_terrain_edits(
+    mut events: EventReader<TerrainEditEvent>,
+    mut edits: ResMut<TerrainEdits>,
+    mesh_config: Res<MeshConfig>,
+    chunks: Query<(&Chunk, &NoiseMap)>,
+) {
+    let mesh_config = mesh_config.sanitized();
+    let size = mesh_config.grid_size;
+    if events.is_empty() {
+        return;
+    }
+
+    edits.resample(size);
+
+    let noise: HashMap<(i32, i32), &NoiseMap> = chunks
+        .iter()
+        .filter(|(_, map)| map.size().0 == size)
+        .map(|(chunk, map)| ((chunk.x, chunk.y), map))
+        .collect();
+    let step = mesh_config.scale / (size - 1) as f32;
+    let height_multiplier = mesh_config.height_multiplier as f64;
+
+    for event in events.iter() {
+        // NOTE heights are stored in noise units, which a flat terrain has no scale for.
+        if height_multiplier == 0.0 && !matches!(event.brush, Brush::Paint(_)) {
+            continue;
+        }
+        // NOTE global grid coordinates, where chunk (x, y) starts at vertex (x, y) * (size - 1).
+        let center = Vec2::new(
+            (event.center.x + mesh_config.scale / 2.0) / step,
+            (mesh_config.scale / 2.0 - event.center.z) / step,
+        );
+        let radius = event.radius / step;
+        let blend = |weight: f32| (event.strength * weight).clamp(0.0, 1.0) as f64;
+
+        // NOTE changes are collected first, so smoothing reads the terrain before this edit.
+        let mut changes = vec![];
+        for y in (center.y - radius).ceil() as i32..=(center.y + radius).floor() as i32 {
+            for x in (center.x - radius).ceil() as i32..=(center.x + radius).floor() as i32 {
+                let distance = Vec2::new(x as f32, y as f32).distance(center);
+                if distance > radius {
+                    continue;
+                }
+                let t = 1.0 - distance / radius.max(f32::EPSILON);
+                let weight = t * t * (3.0 - 2.0 * t);
+
+                let change = match (event.brush, edits.value(&noise, (x, y), size)) {
+                    (Brush::Paint(color), _) => {
+                        edits.add_paint((x, y), color, blend(weight) as f32, size);
+                        continue;
+                    }
+                    (_, None) => continue,
+                    (Brush::Raise, _) => (event.strength * weight) as f64 / height_multiplier,
+                    (Brush::Lower, _) => -(event.strength * weight) as f64 / height_multiplier,
+                    (Brush::Flatten(height), Some(value)) => {
+                        (height as f64 / height_multiplier - value) * blend(weight)
+                    }
+                    (Brush::Smooth, Some(value)) => {
+                        let neighbours: Vec<f64> = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                            .into_iter()
+                            .filter_map(|vertex| edits.value(&noise, vertex, size))
+                            .collect();
+                        if neighbours.is_empty() {
+                            continue;
+                        }
+                        let average = neighbours.iter().sum::<f64>() / neighbours.len() as f64;
+                        (average - value) * blend(weight)
+                    }
+                };
+                changes.push(((x, y), change));
+            }
+        }
+
+        for (vertex, change) in changes {
+            edits.add(vertex, change, size);
+        }
+    }
+}
+
+/// Mesh, texture and heights of a chunk being rebuilt after an edit.
+#[derive(Component)]
+pub(crate) struct ComputeChunkEdit(Task<MeshImageData>);
+
+/// Rebuilds edited chunks in place, so their objects and colliders are kept.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn rebuild_edited_chunks(
+    mut commands: Commands,
+    mut edits: ResMut<TerrainEdits>,
+    mesh_config: Res<MeshConfig>,
+    density_layers: Res<DensityLayers>,
+    mut pool: ResMut<ChunkPool>,
+    mut spawned: ResMut<SpawnedChunks>,
+    chunks: Query<(Entity, &Chunk, &NoiseMap, Option<&ComputeChunkEdit>)>,
+    tasks: Query<(Entity, &ComputeMeshImageData)>,
+) {
+    if edits.dirty.is_empty() {
+        return;
+    }
+    let dirty = std::mem::take(&mut edits.dirty);
+
+    // NOTE chunks still generating started from the old edits, so they are restarted.
+    for (task_entity, task) in &tasks {
+        if dirty.contains(&task.chunk) {
+            spawned.0.remove(&task.chunk);
+            commands.entity(task_entity).despawn_recursive();
+            pool.set_changed();
+        }
+    }
+
+    let thread_pool = AsyncComputeTaskPool::get();
+    let density_config = density_config(&mesh_config, &density_layers);
+    let mesh_config = mesh_config.sanitized();
+    for (entity, chunk, nm, running) in &chunks {
+        let coord = (chunk.x, chunk.y);
+        if !dirty.contains(&coord) {
+            continue;
+        }
+        // NOTE replacing a running rebuild would cancel it, so a chunk that takes longer than
+        // a frame would never update during a stroke. It is rebuilt again once it finishes.
+        if running.is_some() {
+            edits.dirty.insert(coord);
+            continue;
+        }
+
+        let nm = edits.apply(coord, nm);
+        let paint = edits.paint_for(coord, &mesh_config);
+        let mesh_config = mesh_config.clone();
+        let density_config = density_config.clone();
+        let task = thread_pool.spawn(async move {
+            let density =
+                density_config.map(|config| density_noise(coord, mesh_config.grid_size, &config));
+            let mut mesh_image_data = mesh::get_mesh(&nm, density.as_ref(), &mesh_config);
+            if let Some(paint) = paint {
+                paint.composite(&mut mesh_image_data.image);
+            }
+            mesh_image_data
+        });
+        commands.entity(entity).insert(ComputeChunkEdit(task));
+    }
+}
+
+pub(crate) fn apply_chunk_edits(
+    mut commands: Commands,
+    mut chunks: Query<(
+        Entity,
+        &Handle<Mesh>,
+        &Handle<StandardMaterial>,
+        &mut ComputeChunkEdit,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, mesh_handle, material, mut task) in &mut chunks {
+        if let Some(MeshImageData {
+            mesh,
+            image,
+            heights,
+        }) = future::block_on(future::poll_once(&mut task.0))
+        {
+            // NOTE `calculate_bounds` skips entities that have bounds, so raised terrain would
+            // otherwise be culled by the old ones.
+            let mut chunk = commands.entity(entity);
+            match mesh.compute_aabb() {
+                Some(aabb) => chunk.insert(aabb),
+                None => chunk.remove::<Aabb>(),
+            };
+            chunk.insert(heights).remove::<ComputeChunkEdit>();
+            if let Some(chunk_mesh) = meshes.get_mut(mesh_handle) {
+                *chunk_mesh = mesh;
+            }
+            replace_texture(material, image, &mut images, &mut materials);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edits() -> TerrainEdits {
+        let mut edits = TerrainEdits::default();
+        edits.add((1, 1), 0.5, 3);
+        edits.add((2, 0), -0.25, 3);
+        edits.add_paint((1, 1), Color::RED, 1.0, 3);
+        edits
+    }
+
+    fn header(version: u32, count: u32) -> Vec<u8> {
+        let mut bytes = EDITS_MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes
+    }
+
+    fn chunk(bytes: &mut Vec<u8>, (x, y): (i32, i32), size: u32) {
+        bytes.extend_from_slice(&x.to_le_bytes());
+        bytes.extend_from_slice(&y.to_le_bytes());
+        bytes.extend_from_slice(&size.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trip_keeps_edits_and_paint() {
+        let edits = edits();
+        let mut loaded = TerrainEdits::default();
+
+        assert!(loaded.load(&edits.to_bytes()));
+        assert_eq!(loaded.chunks.len(), 4);
+        assert_eq!(loaded.get((0, 0)).unwrap().deltas[4], 0.5);
+        assert_eq!(loaded.get((0, 0)).unwrap().deltas[2], -0.25);
+        assert_eq!(loaded.get((1, 0)).unwrap().deltas[0], -0.25);
+        assert_eq!(
+            loaded.get_paint((0, 0)).unwrap().texels[4],
+            [255, 0, 0, 255]
+        );
+        assert_eq!(loaded.get_paint((0, 0)).unwrap().texels[0], [0; 4]);
+        assert!(loaded.dirty.contains(&(0, 0)) && loaded.dirty.contains(&(1, 0)));
+    }
+
+    #[test]
+    fn reads_version_1_without_paint() {
+        let mut bytes = header(1, 1);
+        chunk(&mut bytes, (-1, 2), 2);
+        for delta in [0.0f32, 1.0, 2.0, 3.0] {
+            bytes.extend_from_slice(&delta.to_le_bytes());
+        }
+        let mut edits = TerrainEdits::default();
+
+        assert!(edits.load(&bytes));
+        assert_eq!(edits.get((-1, 2)).unwrap().deltas, vec![0.0, 1.0, 2.0, 3.0]);
+        assert!(edits.paint.is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_bytes() {
+        let bytes = edits().to_bytes();
+        let mut edits = edits();
+
+        for len in [0, 4, 8, 20, bytes.len() - 1] {
+            assert!(!edits.load(&bytes[..len]));
+        }
+        // NOTE a failed load leaves the current edits untouched.
+        assert_eq!(edits.chunks.len(), 4);
+    }
+
+    #[test]
+    fn rejects_grids_smaller_than_two() {
+        for size in [0, 1] {
+            let mut bytes = header(EDITS_VERSION, 1);
+            chunk(&mut bytes, (0, 0), size);
+            bytes.extend_from_slice(&[0; 4]);
+            bytes.extend_from_slice(&0u32.to_le_bytes());
+
+            assert!(read_edits(&bytes).is_none());
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        assert!(read_edits(&header(0, 0)).is_none());
+        assert!(read_edits(&header(EDITS_VERSION + 1, 0)).is_none());
+    }
+
+    #[test]
+    fn interior_vertex_is_in_one_chunk() {
+        let chunks: Vec<_> = grid_chunks((3, 5), 3).collect();
+
+        assert_eq!(chunks, vec![((1, 2), (1, 1))]);
+    }
+
+    #[test]
+    fn border_vertex_is_shared_by_two_chunks() {
+        let chunks: Vec<_> = grid_chunks((2, 1), 3).collect();
+
+        assert_eq!(chunks, vec![((1, 0), (0, 1)), ((0, 0), (2, 1))]);
+    }
+
+    #[test]
+    fn corner_vertex_is_shared_by_four_chunks() {
+        let chunks: Vec<_> = grid_chunks((0, 0), 3).collect();
+
+        assert_eq!(
+            chunks,
+            vec![
+                ((0, 0), (0, 0)),
+                ((0, -1), (0, 2)),
+                ((-1, 0), (2, 0)),
+                ((-1, -1), (2, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn resampling_keeps_borders() {
+        let edits = ChunkEdits {
+            size: 2,
+            deltas: vec![0.0, 1.0, 2.0, 3.0],
+        };
+        let resampled = edits.resampled(3);
+
+        assert_eq!(
+            resampled.deltas,
+            vec![0.0, 0.5, 1.0, 1.0, 1.5, 2.0, 2.0, 2.5, 3.0]
+        );
+        assert_eq!(resampled.resampled(2).deltas, edits.deltas);
+    }
+
+    #[test]
+    fn apply_resamples_edits_to_the_noise_size() {
+        let edits = ChunkEdits {
+            size: 2,
+            deltas: vec![1.0; 4],
+        };
+        let map = NoiseMap::from_values(3, vec![0.5; 9]);
+
+        assert_eq!(edits.apply(&map).values(), vec![1.5; 9]);
+    }
+
+    #[test]
+    fn resampling_blends_paint() {
+        let paint = ChunkPaint {
+            size: 2,
+            texels: vec![[0; 4], [255, 255, 255, 255], [0; 4], [255, 255, 255, 255]],
+        };
+
+        assert_eq!(paint.resampled(3).texels[1], [128; 4]);
+    }
+}
@@ -0,0 +1,308 @@
+use std::{error::Error, fmt, fs, path::Path};
+
+use image::{DynamicImage, ImageBuffer, ImageError, ImageFormat, Luma, Rgb, Rgba};
+
+#[cfg(feature = "heightmap")]
+use super::heightmap::HeightmapSampler;
+use super::{
+    chunk_noise,
+    edit::TerrainEdits,
+    mesh::{self, MeshConfig, TextureMode},
+    noise::{NoiseConfig, NoiseMap},
+};
+
+#[derive(Clone, Copy)]
+pub enum HeightmapFormat {
+    /// Grayscale PNG spanning [`WorldHeightmap::height_range`].
+    Png16,
+    /// OpenEXR with the world height in every channel.
+    Exr32,
+    /// Little-endian `u16`s spanning [`WorldHeightmap::height_range`], without a header.
+    Raw16,
+    /// Little-endian `f32` world heights, without a header.
+    Raw32,
+}
+
+/// Why a region could not be rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderError {
+    /// `min` lies past `max` along an axis, so the region holds no chunks.
+    EmptyRegion { min: (i32, i32), max: (i32, i32) },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::EmptyRegion { min, max } => {
+                write!(f, "region from {min:?} to {max:?} holds no chunks")
+            }
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+/// Heights and colours of a region of chunks stitched into one grid, with north up.
+pub struct WorldHeightmap {
+    pub width: usize,
+    pub height: usize,
+    /// World heights, row by row from the north edge.
+    pub heights: Vec<f32>,
+    /// Terrain colours including paint, laid out like `heights`.
+    pub colors: Vec<[u8; 4]>,
+}
+
+impl WorldHeightmap {
+    /// Generates the chunks from `min` to `max` inclusive through the same pipeline as the
+    /// terrain, with `edits` applied.
+    pub fn render(
+        min: (i32, i32),
+        max: (i32, i32),
+        noise_config: &NoiseConfig,
+        mesh_config: &MeshConfig,
+        edits: &TerrainEdits,
+    ) -> Result<Self, RenderError> {
+        Self::render_chunks(min, max, mesh_config, edits, |coord, mesh_config| {
+            chunk_noise(
+                coord,
+                noise_config,
+                mesh_config,
+                #[cfg(feature = "heightmap")]
+                None,
+            )
+        })
+    }
+
+    /// Like [`WorldHeightmap::render`], for terrain generated from a heightmap, such as the
+    /// one `LoadedHeightmap::sampler` returns.
+    #[cfg(feature = "heightmap")]
+    pub fn render_with_heightmap(
+        min: (i32, i32),
+        max: (i32, i32),
+        noise_config: &NoiseConfig,
+        mesh_config: &MeshConfig,
+        edits: &TerrainEdits,
+        heightmap: &HeightmapSampler,
+    ) -> Result<Self, RenderError> {
+        Self::render_chunks(min, max, mesh_config, edits, |coord, mesh_config| {
+            chunk_noise(coord, noise_config, mesh_config, Some(heightmap))
+        })
+    }
+
+    fn render_chunks(
+        min: (i32, i32),
+        max: (i32, i32),
+        mesh_config: &MeshConfig,
+        edits: &TerrainEdits,
+        noise: impl Fn((i32, i32), &MeshConfig) -> NoiseMap,
+    ) -> Result<Self, RenderError> {
+        if min.0 > max.0 || min.1 > max.1 {
+            return Err(RenderError::EmptyRegion { min, max });
+        }
+
+        let mut mesh_config = mesh_config.sanitized();
+        mesh_config.texture_mode = TextureMode::Color;
+        let cells = mesh_config.grid_size - 1;
+        let chunks_x = (max.0 as i64 - min.0 as i64 + 1) as usize;
+        let chunks_y = (max.1 as i64 - min.1 as i64 + 1) as usize;
+        let width = chunks_x * cells + 1;
+        let height = chunks_y * cells + 1;
+
+        let mut heights = vec![0.0; width * height];
+        let mut colors = vec![[0; 4]; width * height];
+        for chunk_y in 0..chunks_y {
+            for chunk_x in 0..chunks_x {
+                let coord = (min.0 + chunk_x as i32, min.1 + chunk_y as i32);
+                let nm = noise(coord, &mesh_config);
+                let nm = edits.apply(coord, &nm);
+                let mut image = mesh::get_texture(&nm, None, &mesh_config);
+                if let Some(paint) = edits.paint_for(coord, &mesh_config) {
+                    paint.composite(&mut image);
+                }
+
+                // NOTE border vertices are shared, so neighbouring chunks write them twice.
+                for y in 0..=cells {
+                    for x in 0..=cells {
+                        let column = chunk_x * cells + x;
+                        let row = height - 1 - (chunk_y * cells + y);
+                        let i = row * width + column;
+                        let texel = (y * mesh_config.grid_size + x) * 4;
+                        heights[i] = nm.get_value(x, y) * mesh_config.height_multiplier;
+                        colors[i].copy_from_slice(&image.data[texel..texel + 4]);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            heights,
+            colors,
+        })
+    }
+
+    /// Lowest and highest world height, which 16-bit formats are normalized to.
+    pub fn height_range(&self) -> (f32, f32) {
+        self.heights
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), h| {
+                (min.min(*h), max.max(*h))
+            })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: HeightmapFormat) -> Result<(), ImageError> {
+        let (width, height) = (self.width as u32, self.height as u32);
+        match format {
+            HeightmapFormat::Png16 => {
+                let buffer: ImageBuffer<Luma<u16>, _> =
+                    ImageBuffer::from_raw(width, height, self.normalized())
+                        .expect("heights match the heightmap size");
+                buffer.save_with_format(path, ImageFormat::Png)
+            }
+            HeightmapFormat::Exr32 => {
+                let data = self.heights.iter().flat_map(|h| [*h; 3]).collect();
+                let buffer: ImageBuffer<Rgb<f32>, _> = ImageBuffer::from_raw(width, height, data)
+                    .expect("heights match the heightmap size");
+                DynamicImage::ImageRgb32F(buffer).save_with_format(path, ImageFormat::OpenExr)
+            }
+            HeightmapFormat::Raw16 => {
+                let bytes: Vec<u8> = self
+                    .normalized()
+                    .iter()
+                    .flat_map(|h| h.to_le_bytes())
+                    .collect();
+                Ok(fs::write(path, bytes)?)
+            }
+            HeightmapFormat::Raw32 => {
+                let bytes: Vec<u8> = self.heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+                Ok(fs::write(path, bytes)?)
+            }
+        }
+    }
+
+    /// Saves the colours as an RGBA PNG.
+    pub fn save_color_map(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        let data = self.colors.iter().flatten().copied().collect();
+        let buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
+            ImageBuffer::from_raw(self.width as u32, self.height as u32, data)
+                .expect("colours match the heightmap size");
+        buffer.save_with_format(path, ImageFormat::Png)
+    }
+
+    fn normalized(&self) -> Vec<u16> {
+        let (min, max) = self.height_range();
+        let range = (max - min).max(f32::EPSILON);
+        self.heights
+            .iter()
+            .map(|h| ((h - min) / range * u16::MAX as f32).round() as u16)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn render(min: (i32, i32), max: (i32, i32)) -> Result<WorldHeightmap, RenderError> {
+        let mesh_config = MeshConfig {
+            grid_size: 5,
+            ..MeshConfig::default()
+        };
+        WorldHeightmap::render(
+            min,
+            max,
+            &NoiseConfig::default(),
+            &mesh_config,
+            &TerrainEdits::default(),
+        )
+    }
+
+    fn heightmap() -> WorldHeightmap {
+        WorldHeightmap {
+            width: 3,
+            height: 2,
+            heights: vec![-10.0, 0.0, 10.0, 30.0, 20.0, -10.0],
+            colors: vec![[0; 4]; 6],
+        }
+    }
+
+    fn saved(name: &str, format: HeightmapFormat) -> Vec<u8> {
+        let path = env::temp_dir().join(format!("ymir-export-{}-{name}", std::process::id()));
+        heightmap().save(&path, format).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn region_shares_chunk_borders() {
+        let heightmap = render((-1, 0), (0, 2)).unwrap();
+
+        assert_eq!((heightmap.width, heightmap.height), (9, 13));
+        assert_eq!(heightmap.heights.len(), 9 * 13);
+        assert_eq!(heightmap.colors.len(), 9 * 13);
+    }
+
+    #[test]
+    fn single_chunk_region() {
+        let heightmap = render((3, 3), (3, 3)).unwrap();
+
+        assert_eq!((heightmap.width, heightmap.height), (5, 5));
+    }
+
+    #[test]
+    fn inverted_region_is_an_error() {
+        let error = RenderError::EmptyRegion {
+            min: (1, 0),
+            max: (0, 0),
+        };
+        assert_eq!(render((1, 0), (0, 0)).err(), Some(error));
+        assert!(render((0, 1), (0, 0)).is_err());
+    }
+
+    #[test]
+    fn normalized_spans_height_range() {
+        let heightmap = heightmap();
+
+        assert_eq!(heightmap.height_range(), (-10.0, 30.0));
+        assert_eq!(
+            heightmap.normalized(),
+            vec![0, 16384, 32768, u16::MAX, 49151, 0]
+        );
+    }
+
+    #[test]
+    fn png16_round_trips() {
+        let image = image::load_from_memory(&saved("png16.png", HeightmapFormat::Png16))
+            .unwrap()
+            .into_luma16();
+
+        assert_eq!(image.dimensions(), (3, 2));
+        assert_eq!(image.into_raw(), heightmap().normalized());
+    }
+
+    #[test]
+    fn raw16_is_little_endian_normalized() {
+        let bytes = saved("raw16", HeightmapFormat::Raw16);
+        let values: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|value| u16::from_le_bytes([value[0], value[1]]))
+            .collect();
+
+        assert_eq!(values, heightmap().normalized());
+    }
+
+    #[test]
+    fn raw32_is_little_endian_heights() {
+        let bytes = saved("raw32", HeightmapFormat::Raw32);
+        let values: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+            .collect();
+
+        assert_eq!(values, heightmap().heights);
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+
+use super::{cache::StableHasher, mesh::MeshConfig, noise::NoiseMap};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeightmapWrap {
+    /// The edge pixels continue past the image.
+    #[default]
+    Clamp,
+    /// The image repeats in every direction.
+    Tile,
+}
+
+/// Grayscale image loaded from a `.heightmap.png` or `.heightmap.exr` file, with north up.
+#[derive(TypeUuid)]
+#[uuid = "5b1f3c8e-9a2d-4e47-b6f0-7d3c21a8e914"]
+pub struct Heightmap {
+    pub width: usize,
+    pub height: usize,
+    /// Row by row from the north edge. Integer formats are normalized to `0.0..=1.0`.
+    values: Arc<Vec<f32>>,
+    /// Hash of the file, so cached chunks are regenerated when the image is edited.
+    hash: u64,
+}
+
+#[derive(Default)]
+pub struct HeightmapLoader;
+
+impl AssetLoader for HeightmapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            // NOTE averaged over the channels, so colour images load as their brightness.
+            let image = image::load_from_memory(bytes)?.to_rgb32f();
+            let values = image
+                .pixels()
+                .map(|pixel| pixel.0.iter().sum::<f32>() / 3.0)
+                .collect();
+
+            let mut hasher = StableHasher::default();
+            hasher.write(bytes);
+
+            load_context.set_default_asset(LoadedAsset::new(Heightmap {
+                width: image.width() as usize,
+                height: image.height() as usize,
+                values: Arc::new(values),
+                hash: hasher.finish(),
+            }));
+            Ok(())
+        })
+    }
+
+    // NOTE double extensions, so plain images still load as textures.
+    fn extensions(&self) -> &[&str] {
+        &["heightmap.png", "heightmap.exr"]
+    }
+}
+
+/// Image used as the terrain height in place of the `NoiseConfig` noise.
+#[derive(Resource, Clone)]
+pub struct HeightmapSource {
+    /// Asset path of a `.heightmap.png` or `.heightmap.exr` image.
+    pub path: String,
+    /// World units the image covers, centred on the origin.
+    pub world_size: Vec2,
+    pub wrap: HeightmapWrap,
+    /// Heights of the darkest and brightest pixel, relative to `MeshConfig::height_multiplier`.
+    pub height_range: (f32, f32),
+    /// Weight of the `NoiseConfig` noise added on top as detail, or `0.0` for the image alone.
+    pub detail: f32,
+}
+
+/// Samples a loaded [`Heightmap`] the way its [`HeightmapSource`] describes.
+#[derive(Clone)]
+pub struct HeightmapSampler {
+    source: HeightmapSource,
+    width: usize,
+    height: usize,
+    values: Arc<Vec<f32>>,
+    hash: u64,
+}
+
+impl HeightmapSampler {
+    /// Extends a `noise_key` with the image and how it is mapped onto the world, which
+    /// includes the chunk scale.
+    pub(crate) fn key(&self, noise_key: u64, mesh_config: &MeshConfig) -> u64 {
+        let mut hasher = StableHasher::default();
+        hasher.write(&noise_key.to_le_bytes());
+        hasher.write(&mesh_config.scale.to_le_bytes());
+        hasher.write(&self.hash.to_le_bytes());
+        hasher.write(&self.source.world_size.x.to_le_bytes());
+        hasher.write(&self.source.world_size.y.to_le_bytes());
+        hasher.write(&[self.source.wrap as u8]);
+        hasher.write(&self.source.height_range.0.to_le_bytes());
+        hasher.write(&self.source.height_range.1.to_le_bytes());
+        hasher.write(&self.source.detail.to_le_bytes());
+        hasher.finish()
+    }
+
+    /// Heights of the chunk at `coord`, with `detail` noise of the same size blended in.
+    pub(crate) fn apply(
+        &self,
+        coord: (i32, i32),
+        detail: &NoiseMap,
+        mesh_config: &MeshConfig,
+    ) -> NoiseMap {
+        let (size, _) = detail.size();
+        let cells = (size - 1) as f32;
+        let scale = mesh_config.scale;
+        let step = scale / cells;
+        let (low, high) = self.source.height_range;
+
+        let values = detail
+            .values()
+            .iter()
+            .enumerate()
+            .map(|(i, noise)| {
+                // NOTE the vertex positions of `mesh::get_mesh`, where north is towards -z.
+                let x = coord.0 as f32 * scale + (i % size) as f32 * step - scale / 2.0;
+                let z = -coord.1 as f32 * scale + scale / 2.0 - (i / size) as f32 * step;
+                let uv = (Vec2::new(x, z) + self.source.world_size / 2.0) / self.source.world_size;
+                let height = low + (high - low) * self.sample(uv);
+                height as f64 + self.source.detail as f64 * noise
+            })
+            .collect();
+        NoiseMap::from_values(size, values)
+    }
+
+    /// Bilinearly filtered value at `uv`, where `(0, 0)` is the north-west corner.
+    fn sample(&self, uv: Vec2) -> f32 {
+        let (x, y) = match self.source.wrap {
+            HeightmapWrap::Clamp => {
+                let uv = uv.clamp(Vec2::ZERO, Vec2::ONE);
+                (
+                    uv.x * (self.width - 1) as f32,
+                    uv.y * (self.height - 1) as f32,
+                )
+            }
+            HeightmapWrap::Tile => {
+                let uv = uv - uv.floor();
+                (uv.x * self.width as f32, uv.y * self.height as f32)
+            }
+        };
+
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+        let top = self.get(x0, y0) * (1.0 - tx) + self.get(x0 + 1, y0) * tx;
+        let bottom = self.get(x0, y0 + 1) * (1.0 - tx) + self.get(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    fn get(&self, x: usize, y: usize) -> f32 {
+        let (x, y) = match self.source.wrap {
+            HeightmapWrap::Clamp => (x.min(self.width - 1), y.min(self.height - 1)),
+            HeightmapWrap::Tile => (x % self.width, y % self.height),
+        };
+        self.values[y * self.width + x]
+    }
+}
+
+/// Image of the current [`HeightmapSource`]. Every chunk is rebuilt when it changes.
+#[derive(Resource, Default)]
+pub enum LoadedHeightmap {
+    /// Chunks wait for the image, instead of spawning from the noise alone first.
+    #[default]
+    Loading,
+    Loaded(HeightmapSampler),
+    /// The image could not be loaded, so chunks fall back to the `NoiseConfig` noise.
+    Failed,
+}
+
+impl LoadedHeightmap {
+    /// Sampler of the loaded image, for exporting the terrain with it.
+    pub fn sampler(&self) -> Option<&HeightmapSampler> {
+        match self {
+            Self::Loaded(sampler) => Some(sampler),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn update_loaded_heightmap(
+    source: Res<HeightmapSource>,
+    asset_server: Res<AssetServer>,
+    heightmaps: Res<Assets<Heightmap>>,
+    mut events: EventReader<AssetEvent<Heightmap>>,
+    mut handle: Local<Handle<Heightmap>>,
+    mut loaded: ResMut<LoadedHeightmap>,
+) {
+    if source.is_changed() {
+        *handle = asset_server.load(source.path.as_str());
+        *loaded = LoadedHeightmap::Loading;
+    }
+    // NOTE counted rather than `any`, so every event is read this frame.
+    let reloaded = events
+        .iter()
+        .filter(|event| {
+            matches!(event, AssetEvent::Created { handle: changed }
+                | AssetEvent::Modified { handle: changed } if *changed == *handle)
+        })
+        .count()
+        > 0;
+    // NOTE only checked while loading, as assigning marks the resource changed.
+    if matches!(*loaded, LoadedHeightmap::Loading)
+        && asset_server.get_load_state(&*handle) == LoadState::Failed
+    {
+        warn!(
+            "failed to load heightmap {}, using noise terrain instead",
+            source.path
+        );
+        *loaded = LoadedHeightmap::Failed;
+        return;
+    }
+    if !source.is_changed() && !reloaded {
+        return;
+    }
+    let Some(heightmap) = heightmaps.get(&handle) else {
+        return;
+    };
+
+    *loaded = LoadedHeightmap::Loaded(HeightmapSampler {
+        source: source.clone(),
+        width: heightmap.width,
+        height: heightmap.height,
+        values: heightmap.values.clone(),
+        hash: heightmap.hash,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampler() -> HeightmapSampler {
+        HeightmapSampler {
+            source: HeightmapSource {
+                path: "terrain.heightmap.png".into(),
+                world_size: Vec2::splat(1024.0),
+                wrap: HeightmapWrap::Clamp,
+                height_range: (0.0, 1.0),
+                detail: 0.0,
+            },
+            width: 2,
+            height: 2,
+            values: Arc::new(vec![0.0, 0.25, 0.5, 1.0]),
+            hash: 1,
+        }
+    }
+
+    #[test]
+    fn key_depends_on_scale() {
+        let small = MeshConfig {
+            scale: 128.0,
+            ..default()
+        };
+        let large = MeshConfig {
+            scale: 256.0,
+            ..default()
+        };
+
+        assert_ne!(sampler().key(1, &small), sampler().key(1, &large));
+        assert_eq!(sampler().key(1, &small), sampler().key(1, &small));
+    }
+}
@@ -1,11 +1,18 @@
+#[cfg(feature = "rapier")]
+pub mod brush;
+mod cache;
+pub mod edit;
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(feature = "heightmap")]
+pub mod heightmap;
 pub mod mesh;
 pub mod noise;
+pub mod observer;
+pub mod physics;
 pub mod procspawn;
 pub mod terrain_colors;
 
-use std::f32::consts::FRAC_PI_2;
-
-use ::noise::{Fbm, Perlin};
 use bevy::{
     prelude::*,
     tasks::{AsyncComputeTaskPool, Task},
@@ -14,12 +21,30 @@ use bevy::{
 use bevy_inspector_egui::{Inspectable, InspectorPlugin};
 use futures_lite::future;
 
+#[cfg(feature = "heightmap")]
+use self::heightmap::{
+    update_loaded_heightmap, Heightmap, HeightmapLoader, HeightmapSampler, HeightmapSource,
+    LoadedHeightmap,
+};
+#[cfg(feature = "rapier")]
+use self::{
+    brush::{use_brush, BrushTool},
+    physics::{PhysicsPlugin, RapierBackend},
+};
 use self::{
-    mesh::{MeshConfig, MeshImageData},
-    noise::{NoiseConfig, NoiseMap},
-    procspawn::ProcSpawnPlugin,
+    cache::{DiskCache, NoiseCache},
+    edit::{
+        apply_chunk_edits, apply_terrain_edits, rebuild_edited_chunks, ComputeChunkEdit,
+        TerrainEditEvent, TerrainEdits,
+    },
+    mesh::{MeshConfig, MeshImageData, TextureMode},
+    noise::{DensityLayers, NoiseConfig, NoiseMap},
+    observer::{track_observers, update_observer_chunks, ObserverChunks},
+    procspawn::{GroundCoverRules, ProcSpawnPlugin, SpawnRules},
 };
 
+pub use self::observer::{LoadingShape, TerrainObserver};
+
 #[derive(Component, Default, Inspectable)]
 struct Terrain;
 
@@ -29,17 +54,53 @@ pub struct Chunk {
     y: i32,
 }
 
+impl Chunk {
+    fn within(&self, (x, y): (i32, i32), distance: i32) -> bool {
+        (x - distance..=x + distance).contains(&self.x)
+            && (y - distance..=y + distance).contains(&self.y)
+    }
+}
+
 #[derive(Resource)]
 struct ChunkDistance(i32);
 #[derive(Resource)]
 struct ObjectDistance(i32);
+#[derive(Resource)]
+struct PhysicsDistance(i32);
+#[derive(Resource)]
+struct GroundCoverDistance(i32);
+
+/// Limits on chunk generation work, where `0` means unbounded.
+#[derive(Resource)]
+struct ChunkTaskBudget {
+    max_tasks: usize,
+    uploads_per_frame: usize,
+}
 
 #[derive(Default)]
 pub struct YmirPlugin {
     pub chunk_distance: i32,
     pub object_distance: i32,
+    pub physics_distance: i32,
+    /// Chunks around each observer covered by `ground_cover_rules`.
+    pub ground_cover_distance: i32,
+    /// Most chunk tasks computing at once, or `0` to start every missing chunk right away.
+    pub max_chunk_tasks: usize,
+    /// Most finished chunks spawned per frame, or `0` to spawn all of them.
+    pub chunk_uploads_per_frame: usize,
+    /// Chunks whose noise is kept after generation, or `0` to disable the cache.
+    pub noise_cache_size: usize,
+    /// Directory inside the asset folder chunk noise is persisted to between launches, or
+    /// `None` to only cache it in memory.
+    pub disk_cache: Option<String>,
+    /// Image used as the terrain height, or `None` to generate it from `noise_config` alone.
+    #[cfg(feature = "heightmap")]
+    pub heightmap: Option<HeightmapSource>,
     pub mesh_config: MeshConfig,
     pub noise_config: NoiseConfig,
+    pub density_layers: DensityLayers,
+    pub spawn_rules: SpawnRules,
+    pub ground_cover_rules: GroundCoverRules,
     pub inspectors: bool,
 }
 
@@ -47,23 +108,67 @@ impl Plugin for YmirPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup)
             .add_event::<PlayerPositionChangedEvent>()
+            .add_event::<TerrainEditEvent>()
             .insert_resource(self.mesh_config.clone())
             .insert_resource(self.noise_config.clone())
-            .insert_resource(PlayerChunk((0, 0)))
+            .insert_resource(self.density_layers.clone())
+            .insert_resource(self.spawn_rules.clone())
+            .insert_resource(self.ground_cover_rules.clone())
+            .init_resource::<PlayerChunk>()
+            .init_resource::<ObserverChunks>()
             .insert_resource(ChunkDistance(self.chunk_distance))
             .insert_resource(ObjectDistance(self.object_distance))
+            .insert_resource(PhysicsDistance(self.physics_distance))
+            .insert_resource(GroundCoverDistance(self.ground_cover_distance))
+            .insert_resource(ChunkTaskBudget {
+                max_tasks: self.max_chunk_tasks,
+                uploads_per_frame: self.chunk_uploads_per_frame,
+            })
             .insert_resource(ChunkPool(HashSet::new()))
             .insert_resource(SpawnedChunks(HashSet::new()))
+            .init_resource::<ChunkQueue>()
+            .init_resource::<TerrainEdits>()
+            .insert_resource(NoiseCache::new(self.noise_cache_size))
+            .insert_resource(DiskCache(self.disk_cache.clone()))
             .add_system(spawn_tasks)
-            .add_system(remove_terrain.label("ymir_cleanup"))
+            .add_system(regenerate_terrain.label("ymir_cleanup"))
             .add_system(spawn_chunks.after("ymir_cleanup"))
+            .add_system(apply_chunk_textures)
+            .add_system(apply_terrain_edits.label("ymir_edit"))
+            .add_system(rebuild_edited_chunks.after("ymir_edit"))
+            .add_system(apply_chunk_edits)
+            .add_system(cancel_chunk_tasks)
             .add_system(register_player_chunk)
+            .add_system(track_observers)
+            .add_system(update_observer_chunks)
             .add_system(update_chunk_pool)
+            // NOTE after `Update`, so no system there inserts onto a chunk despawned this frame.
+            .add_system_to_stage(CoreStage::PostUpdate, despawn_distant_chunks)
+            .add_system_to_stage(CoreStage::PostUpdate, despawn_replaced_chunks)
             .add_plugin(ProcSpawnPlugin);
 
+        #[cfg(feature = "rapier")]
+        app.add_plugin(PhysicsPlugin::<RapierBackend>::default())
+            .init_resource::<BrushTool>()
+            .add_system(use_brush.before("ymir_edit"));
+
+        #[cfg(feature = "heightmap")]
+        {
+            app.add_asset::<Heightmap>()
+                .init_asset_loader::<HeightmapLoader>();
+            if let Some(heightmap) = &self.heightmap {
+                app.insert_resource(heightmap.clone())
+                    .init_resource::<LoadedHeightmap>()
+                    .add_system(update_loaded_heightmap);
+            }
+        }
+
         if self.inspectors {
             app.add_plugin(InspectorPlugin::<MeshConfig>::new_insert_manually());
             app.add_plugin(InspectorPlugin::<NoiseConfig>::new_insert_manually());
+            app.add_plugin(InspectorPlugin::<DensityLayers>::new_insert_manually());
+            #[cfg(feature = "rapier")]
+            app.add_plugin(InspectorPlugin::<BrushTool>::new_insert_manually());
         }
     }
 }
@@ -79,93 +184,314 @@ fn setup(mut commands: Commands) {
     ));
 }
 
-fn remove_terrain(
+/// Rebuilds chunks after a config change. Old chunks stay visible until their replacement is
+/// spawned, and changes that only affect the texture re-bake it on the existing chunks.
+#[allow(clippy::too_many_arguments)]
+fn regenerate_terrain(
     mut commands: Commands,
-    query: Query<Entity, With<Terrain>>,
     mesh_config: Res<MeshConfig>,
     noise_config: Res<NoiseConfig>,
+    density_layers: Res<DensityLayers>,
+    mut edits: ResMut<TerrainEdits>,
+    mut last_mesh_config: Local<Option<MeshConfig>>,
+    mut pool: ResMut<ChunkPool>,
     mut spawned: ResMut<SpawnedChunks>,
+    chunks: Query<(Entity, &Chunk, &NoiseMap, Option<&ComputeChunkEdit>)>,
+    tasks: Query<(Entity, &ComputeMeshImageData)>,
+    #[cfg(feature = "heightmap")] heightmap: Option<Res<LoadedHeightmap>>,
 ) {
-    if mesh_config.is_changed() || noise_config.is_changed() {
-        for terrain in &query {
-            commands.entity(terrain).despawn_descendants();
-            spawned.0.clear();
+    let density_preview = matches!(mesh_config.texture_mode, TextureMode::Density(_));
+    #[cfg(feature = "heightmap")]
+    let heights_changed =
+        noise_config.is_changed() || heightmap.is_some_and(|heightmap| heightmap.is_changed());
+    #[cfg(not(feature = "heightmap"))]
+    let heights_changed = noise_config.is_changed();
+    if !mesh_config.is_changed()
+        && !heights_changed
+        && !(density_preview && density_layers.is_changed())
+    {
+        return;
+    }
+    // NOTE nothing is spawned yet when the configs are first inserted.
+    let Some(last_mesh_config) = last_mesh_config.replace(mesh_config.clone()) else {
+        return;
+    };
+
+    // NOTE tasks started with the old config would spawn outdated chunks, so they are restarted.
+    for (task_entity, task) in &tasks {
+        spawned.0.remove(&task.chunk);
+        commands.entity(task_entity).despawn_recursive();
+    }
+    pool.set_changed();
+
+    if heights_changed || !last_mesh_config.same_geometry(&mesh_config) {
+        // NOTE chunks outside the pool are left to `despawn_distant_chunks`, as it changed above.
+        for (entity, _, _, _) in &chunks {
+            commands
+                .entity(entity)
+                .remove::<ComputeChunkTexture>()
+                .remove::<ComputeChunkEdit>();
+        }
+        spawned.0.clear();
+        return;
+    }
+
+    // NOTE textures are re-baked from the noise kept on each chunk, only a previewed density
+    // layer is sampled again.
+    let thread_pool = AsyncComputeTaskPool::get();
+    let density_config = density_config(&mesh_config, &density_layers);
+    let mesh_config = mesh_config.sanitized();
+    for (entity, chunk, nm, editing) in &chunks {
+        let coord = (chunk.x, chunk.y);
+        // NOTE a running edit bakes the old colours and would finish after this texture, so
+        // it is restarted with the new config instead.
+        if editing.is_some() {
+            commands.entity(entity).remove::<ComputeChunkEdit>();
+            edits.mark_dirty(coord);
+            continue;
         }
+        let nm = edits.apply(coord, nm);
+        let paint = edits.paint_for(coord, &mesh_config);
+        let mesh_config = mesh_config.clone();
+        let density_config = density_config.clone();
+        let task = thread_pool.spawn(async move {
+            let density =
+                density_config.map(|config| density_noise(coord, mesh_config.grid_size, &config));
+            let mut image = mesh::get_texture(&nm, density.as_ref(), &mesh_config);
+            if let Some(paint) = paint {
+                paint.composite(&mut image);
+            }
+            image
+        });
+        commands.entity(entity).insert(ComputeChunkTexture(task));
+    }
+}
+
+/// Noise config of the density layer previewed by `TextureMode::Density`, if any.
+fn density_config(mesh_config: &MeshConfig, density_layers: &DensityLayers) -> Option<NoiseConfig> {
+    match mesh_config.texture_mode {
+        TextureMode::Density(layer) => density_layers.layers.get(layer).cloned(),
+        _ => None,
     }
 }
 
+fn terrain_noise(coord: (i32, i32), grid_size: usize, config: &NoiseConfig) -> NoiseMap {
+    NoiseMap::new(
+        &config.fbm(),
+        grid_size,
+        coord,
+        config.offset,
+        config.falloff,
+    )
+}
+
+/// Unedited heights of a chunk, from the heightmap if there is one, otherwise the noise.
+fn chunk_noise(
+    coord: (i32, i32),
+    noise_config: &NoiseConfig,
+    mesh_config: &MeshConfig,
+    #[cfg(feature = "heightmap")] heightmap: Option<&HeightmapSampler>,
+) -> NoiseMap {
+    let nm = terrain_noise(coord, mesh_config.grid_size, noise_config);
+    #[cfg(feature = "heightmap")]
+    if let Some(heightmap) = heightmap {
+        return heightmap.apply(coord, &nm, mesh_config);
+    }
+    nm
+}
+
+fn density_noise(coord: (i32, i32), grid_size: usize, config: &NoiseConfig) -> NoiseMap {
+    NoiseMap::new(&config.fbm(), grid_size, coord, config.offset, false)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_tasks(
     mut commands: Commands,
     query: Query<Entity, With<Terrain>>,
     mesh_config: Res<MeshConfig>,
     noise_config: Res<NoiseConfig>,
+    density_layers: Res<DensityLayers>,
     pool: Res<ChunkPool>,
+    observer_chunks: Res<ObserverChunks>,
+    mut queue: ResMut<ChunkQueue>,
     mut spawned: ResMut<SpawnedChunks>,
+    budget: Res<ChunkTaskBudget>,
+    mut noise_cache: ResMut<NoiseCache>,
+    disk_cache: Res<DiskCache>,
+    asset_server: Res<AssetServer>,
+    edits: Res<TerrainEdits>,
+    tasks: Query<(), With<ComputeMeshImageData>>,
+    #[cfg(feature = "heightmap")] heightmap: Option<Res<LoadedHeightmap>>,
 ) {
-    if !pool.is_changed() {
+    if pool.is_changed() {
+        queue.0 = pool
+            .0
+            .iter()
+            .filter(|coord| !spawned.0.contains(*coord))
+            .cloned()
+            .collect();
+        // NOTE sorted back to front, so the most urgent chunk is popped first.
+        queue.0.sort_by(|a, b| {
+            observer_chunks
+                .priority(*b)
+                .total_cmp(&observer_chunks.priority(*a))
+        });
+    }
+    if queue.0.is_empty() {
         return;
     }
-    let Ok(entity, ) = query.get_single() else { return;};
+    let Ok(entity) = query.get_single() else {
+        return;
+    };
     let thread_pool = AsyncComputeTaskPool::get();
 
-    let to_spawn = pool
-        .0
-        .iter()
-        .filter(|coord| !spawned.0.contains(*coord))
-        .cloned()
-        .collect::<Vec<(i32, i32)>>();
-
-    let NoiseConfig {
-        seed,
-        octaves,
-        frequency,
-        lacunarity,
-        persistence,
-        offset,
-        falloff,
-    } = *noise_config;
-    for (x, y) in to_spawn {
+    let density_config = density_config(&mesh_config, &density_layers);
+    let mesh_config = mesh_config.sanitized();
+    let noise_key = cache::noise_key(&noise_config, mesh_config.grid_size);
+    #[cfg(feature = "heightmap")]
+    let (heightmap, noise_key) = match heightmap.as_deref() {
+        Some(LoadedHeightmap::Loading) => return,
+        Some(LoadedHeightmap::Loaded(sampler)) => {
+            (Some(sampler.clone()), sampler.key(noise_key, &mesh_config))
+        }
+        Some(LoadedHeightmap::Failed) | None => (None, noise_key),
+    };
+    let available = match budget.max_tasks {
+        0 => usize::MAX,
+        max => max.saturating_sub(tasks.iter().count()),
+    };
+    for _ in 0..available {
+        let Some((x, y)) = queue.0.pop() else { break };
         let mesh_config = mesh_config.clone();
+        let noise_config = noise_config.clone();
+        let density_config = density_config.clone();
+        #[cfg(feature = "heightmap")]
+        let heightmap = heightmap.clone();
+        let cached = noise_cache.get((x, y), noise_key);
+        let chunk_edits = edits.get((x, y)).cloned();
+        let paint = edits.paint_for((x, y), &mesh_config);
+        let disk = disk_cache
+            .chunk_path(noise_config.seed, noise_key, (x, y))
+            .map(|path| (asset_server.clone(), path));
         let task = thread_pool.spawn(async move {
-            let mut fbm: Fbm<Perlin> = Fbm::new(seed);
-            fbm.frequency = frequency;
-            fbm.lacunarity = lacunarity;
-            fbm.persistence = persistence;
-            fbm.octaves = octaves;
-
-            let nm = NoiseMap::new(&fbm, mesh_config.grid_size, (x, y), offset, falloff);
-            ((x, y), mesh::get_mesh(&nm, &mesh_config))
+            let generate = || {
+                chunk_noise(
+                    (x, y),
+                    &noise_config,
+                    &mesh_config,
+                    #[cfg(feature = "heightmap")]
+                    heightmap.as_ref(),
+                )
+            };
+            let nm = match (cached, disk) {
+                (Some(nm), _) => nm,
+                (None, Some((asset_server, path))) => {
+                    cache::load_or_generate(
+                        &asset_server,
+                        &path,
+                        noise_key,
+                        mesh_config.grid_size,
+                        generate,
+                    )
+                    .await
+                }
+                (None, None) => generate(),
+            };
+            let density =
+                density_config.map(|config| density_noise((x, y), mesh_config.grid_size, &config));
+            // NOTE the chunk keeps its unedited noise, so edits can be re-applied later.
+            let edited = chunk_edits.map(|chunk_edits| chunk_edits.apply(&nm));
+            let mut mesh_image_data = mesh::get_mesh(
+                edited.as_ref().unwrap_or(&nm),
+                density.as_ref(),
+                &mesh_config,
+            );
+            if let Some(paint) = paint {
+                paint.composite(&mut mesh_image_data.image);
+            }
+            (nm, mesh_image_data)
         });
-        spawned.0.insert((x, y)); // TODO make a system that removes very distant chunks.
+        spawned.0.insert((x, y));
         commands.entity(entity).with_children(|children| {
-            children.spawn((ComputeMeshImageData(task),));
+            children.spawn((ComputeMeshImageData {
+                chunk: (x, y),
+                noise_key,
+                task,
+            },));
         });
     }
 }
 
 #[derive(Component)]
-struct ComputeMeshImageData(Task<((i32, i32), MeshImageData)>);
+struct ComputeMeshImageData {
+    chunk: (i32, i32),
+    noise_key: u64,
+    task: Task<(NoiseMap, MeshImageData)>,
+}
 
+/// Drops the tasks of chunks that left the pool before they finished, which cancels them.
+fn cancel_chunk_tasks(
+    mut commands: Commands,
+    pool: Res<ChunkPool>,
+    mut spawned: ResMut<SpawnedChunks>,
+    tasks: Query<(Entity, &ComputeMeshImageData)>,
+) {
+    if !pool.is_changed() {
+        return;
+    }
+
+    for (task_entity, task) in &tasks {
+        if !pool.0.contains(&task.chunk) {
+            spawned.0.remove(&task.chunk);
+            commands.entity(task_entity).despawn_recursive();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_chunks(
     mut commands: Commands,
     query: Query<Entity, With<Terrain>>,
     mesh_config: Res<MeshConfig>,
+    pool: Res<ChunkPool>,
     mut tasks: Query<(Entity, &mut ComputeMeshImageData)>,
+    chunks: Query<(Entity, &Chunk)>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut images: ResMut<Assets<Image>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    budget: Res<ChunkTaskBudget>,
+    mut noise_cache: ResMut<NoiseCache>,
 ) {
-    let Ok(entity) = query.get_single() else { return;};
+    let Ok(entity) = query.get_single() else {
+        return;
+    };
+    let mut uploads = 0;
     for (task_entity, mut task) in &mut tasks {
+        // NOTE finished tasks beyond the budget keep their result until a later frame.
+        if budget.uploads_per_frame != 0 && uploads >= budget.uploads_per_frame {
+            break;
+        }
+        // NOTE left to `cancel_chunk_tasks`, as the despawn is deferred to the end of the stage.
+        if !pool.0.contains(&task.chunk) {
+            continue;
+        }
+        let (x, y) = task.chunk;
         if let Some((
-            (x, y),
+            nm,
             MeshImageData {
                 mesh,
                 image,
-                collider,
+                heights,
             },
-        )) = futures_lite::future::block_on(future::poll_once(&mut task.0))
+        )) = futures_lite::future::block_on(future::poll_once(&mut task.task))
         {
+            // NOTE swapped in the same frame, so regenerated chunks never leave a hole.
+            for (old_entity, chunk) in &chunks {
+                if (chunk.x, chunk.y) == (x, y) {
+                    commands.entity(old_entity).insert(ReplacedChunk);
+                }
+            }
+
             commands.entity(entity).with_children(|children| {
                 let material = StandardMaterial {
                     base_color_texture: Some(images.add(image)),
@@ -176,7 +502,7 @@ fn spawn_chunks(
                     ..default()
                 };
 
-                let scale = mesh_config.scale;
+                let scale = mesh_config.sanitized().scale;
 
                 let mut mesh = children.spawn(PbrBundle {
                     mesh: meshes.add(mesh),
@@ -189,42 +515,64 @@ fn spawn_chunks(
                     .insert(Chunk { x, y })
                     .insert(DistanceOcclusion);
 
-                match collider {
-                    Some(col) => {
-                        mesh.with_children(|children| {
-                            let mut transform = Transform::from_scale({
-                                let mut vec = Vec3::ONE;
-                                vec.z = -vec.z;
-                                vec.x = -vec.x;
-                                vec
-                            });
-                            transform.rotation =
-                                Quat::from_euler(EulerRot::XYZ, 0.0, -FRAC_PI_2, 0.0);
-
-                            children
-                                .spawn(TransformBundle {
-                                    local: transform,
-                                    ..default()
-                                })
-                                .insert(col);
-                        });
-                    }
-                    None => todo!(),
-                };
+                noise_cache.insert((x, y), task.noise_key, nm.clone());
+                mesh.insert(heights).insert(nm);
             });
 
             commands.entity(task_entity).despawn_recursive();
+            uploads += 1;
+        }
+    }
+}
+
+/// Texture being re-baked for an existing chunk.
+#[derive(Component)]
+struct ComputeChunkTexture(Task<Image>);
+
+fn apply_chunk_textures(
+    mut commands: Commands,
+    mut chunks: Query<(Entity, &Handle<StandardMaterial>, &mut ComputeChunkTexture)>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, material, mut task) in &mut chunks {
+        if let Some(image) = futures_lite::future::block_on(future::poll_once(&mut task.0)) {
+            commands.entity(entity).remove::<ComputeChunkTexture>();
+            replace_texture(material, image, &mut images, &mut materials);
         }
     }
 }
 
+fn replace_texture(
+    material: &Handle<StandardMaterial>,
+    image: Image,
+    images: &mut Assets<Image>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    // NOTE overwritten in place when possible, so the material itself is left untouched.
+    let texture = materials
+        .get(material)
+        .and_then(|material| material.base_color_texture.as_ref())
+        .and_then(|texture| images.get_mut(texture));
+    match texture {
+        Some(texture) => *texture = image,
+        None => {
+            if let Some(material) = materials.get_mut(material) {
+                material.base_color_texture = Some(images.add(image));
+            }
+        }
+    }
+}
+
+/// Moves the player, an implicit observer using `YmirPlugin::chunk_distance`. Prefer tagging
+/// entities with [`TerrainObserver`], which are tracked without any events.
 pub struct PlayerPositionChangedEvent(pub Vec3);
 
 #[derive(Component)]
 struct DistanceOcclusion;
 
-#[derive(Resource, Eq, PartialEq)]
-struct PlayerChunk((i32, i32));
+#[derive(Resource, Default, Eq, PartialEq)]
+struct PlayerChunk(Option<(i32, i32)>);
 
 fn register_player_chunk(
     mut player_chunk: ResMut<PlayerChunk>,
@@ -232,45 +580,80 @@ fn register_player_chunk(
     mesh_config: Res<MeshConfig>,
     mut events: EventReader<PlayerPositionChangedEvent>,
 ) {
-    let Ok(_) = terrain.get_single() else {return;};
-    let chunk_size = mesh_config.scale;
+    let Ok(_) = terrain.get_single() else {
+        return;
+    };
+    let scale = mesh_config.sanitized().scale;
     for event in events.iter() {
-        let mut pos = event.0;
-        pos.x += chunk_size / 2.0;
-        pos.z += chunk_size / 2.0;
-        pos.x /= chunk_size;
-        pos.z /= chunk_size;
-        let coord = Vec2::new(pos.x.floor(), pos.z.floor());
-        let new_chunk_candidate = PlayerChunk((coord.x as i32, -coord.y as i32));
+        let new_chunk_candidate = PlayerChunk(Some(world_to_chunk(event.0, scale)));
         if *player_chunk != new_chunk_candidate {
             *player_chunk = new_chunk_candidate;
         }
     }
 }
 
-fn update_chunk_pool(
-    player_chunk: Res<PlayerChunk>,
-    chunk_distance: Res<ChunkDistance>,
-    mut pool: ResMut<ChunkPool>,
-) {
-    if player_chunk.is_changed() {
-        pool.0.clear();
+fn world_to_chunk(mut pos: Vec3, chunk_size: f32) -> (i32, i32) {
+    pos.x += chunk_size / 2.0;
+    pos.z += chunk_size / 2.0;
+    pos.x /= chunk_size;
+    pos.z /= chunk_size;
+    let coord = Vec2::new(pos.x.floor(), pos.z.floor());
+    (coord.x as i32, -coord.y as i32)
+}
 
-        let dist = chunk_distance.0;
-        let (x, y) = player_chunk.0;
+fn update_chunk_pool(observer_chunks: Res<ObserverChunks>, mut pool: ResMut<ChunkPool>) {
+    if observer_chunks.is_changed() {
+        pool.0.clear();
 
-        for nx in x - dist..=x + dist {
-            for ny in y - dist..=y + dist {
-                if !pool.0.contains(&(nx, ny)) {
-                    pool.0.insert((nx, ny));
+        for area in &observer_chunks.0 {
+            let ((x, y), dist) = (area.chunk, area.chunk_distance);
+            for nx in x - dist..=x + dist {
+                for ny in y - dist..=y + dist {
+                    if area.contains((nx, ny)) {
+                        pool.0.insert((nx, ny));
+                    }
                 }
             }
         }
     }
 }
 
+/// Chunk replaced by a regenerated one, despawned in `CoreStage::PostUpdate` like distant chunks.
+#[derive(Component)]
+struct ReplacedChunk;
+
+fn despawn_replaced_chunks(mut commands: Commands, chunks: Query<Entity, With<ReplacedChunk>>) {
+    for entity in &chunks {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Despawns chunks that left the pool, so they are generated again once they return to it.
+fn despawn_distant_chunks(
+    mut commands: Commands,
+    pool: Res<ChunkPool>,
+    mut spawned: ResMut<SpawnedChunks>,
+    chunks: Query<(Entity, &Chunk)>,
+) {
+    if !pool.is_changed() {
+        return;
+    }
+
+    for (entity, chunk) in &chunks {
+        let coord = (chunk.x, chunk.y);
+        if !pool.0.contains(&coord) {
+            spawned.0.remove(&coord);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
 #[derive(Resource)]
 struct ChunkPool(HashSet<(i32, i32)>);
 
 #[derive(Resource)]
 struct SpawnedChunks(HashSet<(i32, i32)>);
+
+/// Chunks of the pool still waiting for a generation task, rebuilt whenever the pool changes.
+#[derive(Resource, Default)]
+struct ChunkQueue(Vec<(i32, i32)>);
@@ -2,9 +2,9 @@ use bevy::prelude::*;
 use bevy::render::mesh::Indices;
 use bevy::render::render_resource::{Extent3d, PrimitiveTopology, TextureDimension, TextureFormat};
 use bevy_inspector_egui::Inspectable;
-use bevy_rapier3d::prelude::*;
 
 use super::noise::NoiseMap;
+use super::physics::ChunkHeights;
 use super::terrain_colors::TerrainColor;
 
 struct MeshData {
@@ -18,11 +18,13 @@ struct MeshData {
 pub struct MeshConfig {
     #[inspectable(min = 2, max = 1025)]
     pub grid_size: usize,
+    #[inspectable(min = 1.0)]
     pub scale: f32,
     pub height_multiplier: f32,
     pub render_mode: RenderMode,
     pub texture_mode: TextureMode,
     pub flat_shading: bool,
+    pub collider_mode: ColliderMode,
     pub color_config: ColorConfig,
 }
 
@@ -35,11 +37,34 @@ impl Default for MeshConfig {
             render_mode: default(),
             texture_mode: default(),
             flat_shading: true,
+            collider_mode: default(),
             color_config: default(),
         }
     }
 }
 
+impl MeshConfig {
+    /// Copy of the config clamped to values chunk generation can handle, so that no
+    /// configuration picked in the inspector makes chunk generation panic.
+    pub fn sanitized(&self) -> Self {
+        let mut config = self.clone();
+        config.grid_size = config.grid_size.max(2);
+        config.scale = config.scale.max(f32::EPSILON);
+        config
+    }
+
+    /// Whether chunks built with `other` only differ from chunks built with `self` in their
+    /// texture.
+    pub fn same_geometry(&self, other: &MeshConfig) -> bool {
+        self.grid_size == other.grid_size
+            && self.scale == other.scale
+            && self.height_multiplier == other.height_multiplier
+            && self.render_mode == other.render_mode
+            && self.flat_shading == other.flat_shading
+            && self.collider_mode == other.collider_mode
+    }
+}
+
 #[derive(Inspectable, Default, Clone)]
 pub struct ColorRange {
     pub color: Color,
@@ -84,61 +109,96 @@ impl Default for ColorConfig {
     }
 }
 
+impl ColorConfig {
+    /// Colour of the band a raw noise value falls into, if any.
+    pub fn color_at(&self, value: f64) -> Option<Color> {
+        self.band_at(value).map(|band| self.colors[band].color)
+    }
+
+    /// Index into `colors` of the band a raw noise value falls into, if any.
+    pub fn band_at(&self, value: f64) -> Option<usize> {
+        let value = (value * 0.5 + 0.5).clamp(0.0, 1.0);
+
+        // NOTE reversed so the first of several equal bands wins, as `max_by` keeps the last.
+        self.colors
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, color)| value > color.start_height as f64)
+            .max_by(|(_, a), (_, b)| a.start_height.total_cmp(&b.start_height))
+            .map(|(band, _)| band)
+    }
+}
+
 pub struct MeshImageData {
     pub mesh: Mesh,
     pub image: Image,
-    pub collider: Option<Collider>,
+    pub heights: ChunkHeights,
 }
 
-#[derive(Default, Clone, Copy, Inspectable)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Inspectable)]
 pub enum RenderMode {
     #[default]
     Mesh,
     Plane,
 }
+/// Collider of `RenderMode::Mesh` chunks. Chunks in `RenderMode::Plane` get a flat cuboid
+/// instead, unless this is `None`.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Inspectable)]
+pub enum ColliderMode {
+    /// Cheapest option, follows the chunk heights exactly.
+    #[default]
+    Heightfield,
+    /// Built from the chunk mesh triangles, for terrain a heightfield cannot express.
+    Trimesh,
+    None,
+}
 #[derive(Default, Clone, Copy, Inspectable)]
 pub enum TextureMode {
     #[default]
     Color,
     HeightMap(Color),
+    /// Previews the density layer with the given index.
+    Density(usize),
 }
 
-pub fn get_mesh(map: &NoiseMap, mesh_config: &MeshConfig) -> MeshImageData {
+pub fn get_mesh(
+    map: &NoiseMap,
+    density: Option<&NoiseMap>,
+    mesh_config: &MeshConfig,
+) -> MeshImageData {
     match mesh_config.render_mode {
-        RenderMode::Plane => generate_plane(map, mesh_config.scale, mesh_config),
-        RenderMode::Mesh => generate_mesh(map, mesh_config),
+        RenderMode::Plane => generate_plane(map, density, mesh_config.scale, mesh_config),
+        RenderMode::Mesh => generate_mesh(map, density, mesh_config),
     }
 }
-fn generate_plane(map: &NoiseMap, scale: f32, mesh_config: &MeshConfig) -> MeshImageData {
-    let size = map.size().0 as u32;
-
-    let data = match mesh_config.texture_mode {
-        TextureMode::Color => to_color_vec(map, &mesh_config.color_config),
-        TextureMode::HeightMap(color) => to_heightmap_vec(map, color),
-    };
-
+fn generate_plane(
+    map: &NoiseMap,
+    density: Option<&NoiseMap>,
+    scale: f32,
+    mesh_config: &MeshConfig,
+) -> MeshImageData {
     let mesh = Mesh::from(shape::Plane { size: scale });
 
-    let image = Image::new_fill(
-        Extent3d {
-            width: size,
-            height: size,
-            depth_or_array_layers: 1,
-        },
-        TextureDimension::D2,
-        data.as_slice(),
-        TextureFormat::Rgba8UnormSrgb,
-    );
-    let collider = None;
+    let image = get_texture(map, density, mesh_config);
+    let heights = ChunkHeights {
+        size: 2,
+        scale,
+        heights: vec![0.0; 4],
+    };
 
     MeshImageData {
         mesh,
         image,
-        collider,
+        heights,
     }
 }
 
-fn generate_mesh(map: &NoiseMap, mesh_config: &MeshConfig) -> MeshImageData {
+fn generate_mesh(
+    map: &NoiseMap,
+    density: Option<&NoiseMap>,
+    mesh_config: &MeshConfig,
+) -> MeshImageData {
     let size = map.size().0 as u32;
 
     let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
@@ -154,33 +214,18 @@ fn generate_mesh(map: &NoiseMap, mesh_config: &MeshConfig) -> MeshImageData {
         mesh.compute_flat_normals();
     }
 
-    let texture_data = match mesh_config.texture_mode {
-        TextureMode::Color => to_color_vec(map, &mesh_config.color_config),
-        TextureMode::HeightMap(color) => to_heightmap_vec(map, color),
-    };
+    let image = get_texture(map, density, mesh_config);
 
-    let image = Image::new_fill(
-        Extent3d {
-            width: size,
-            height: size,
-            depth_or_array_layers: 1,
-        },
-        TextureDimension::D2,
-        texture_data.as_slice(),
-        TextureFormat::Rgba8UnormSrgb,
-    );
-
-    let collider = Collider::heightfield(
-        mesh_data.heights,
-        size as usize,
-        size as usize,
-        Vec3::ONE * mesh_config.scale,
-    );
+    let heights = ChunkHeights {
+        size: size as usize,
+        scale: mesh_config.scale,
+        heights: mesh_data.heights,
+    };
 
     MeshImageData {
         mesh,
         image,
-        collider: Some(collider),
+        heights,
     }
 }
 
@@ -195,54 +240,83 @@ fn generate_mesh_data(map: &NoiseMap, mesh_config: &MeshConfig) -> MeshData {
     let mut heights = vec![0.0; height * width];
     let mut vertices = vec![[0.0; 3]; height * width];
 
-    let mut indices = vec![0; (height - 1) * (width - 1) * 6];
-
     let mut uvs = vec![[0.0; 2]; height * width];
 
     let mut vertex_index = 0;
-    let mut triangle_index = 0;
-
-    let mut add_triangle = |a: usize, b: usize, c: usize| {
-        indices[triangle_index] = a as u32;
-        indices[triangle_index + 1] = b as u32;
-        indices[triangle_index + 2] = c as u32;
-        triangle_index += 3;
-    };
 
     for y in 0..height {
         for x in 0..width {
             let xf = x as f32;
             let zf = y as f32;
             let height_value = map.get_value(x, y) * mesh_config.height_multiplier;
-            heights[vertex_index] = map.get_value(x, y) / mesh_config.scale * mesh_config.height_multiplier;
+            heights[vertex_index] = height_value;
             vertices[vertex_index] = [
                 (top_left_x + xf) / (width - 1) as f32 * scale,
                 height_value,
                 (top_left_z - zf) / (height - 1) as f32 * scale,
-            ]; 
+            ];
             uvs[vertex_index] = [
                 x as f32 / (width - 1) as f32,
                 y as f32 / (height - 1) as f32,
             ];
 
-            if x < width - 1 && y < height - 1 {
-                add_triangle(vertex_index, vertex_index + width + 1, vertex_index + width);
-                add_triangle(vertex_index + width + 1, vertex_index, vertex_index + 1);
-            }
-
             vertex_index += 1;
         }
     }
 
     MeshData {
         vertices,
-        indices,
+        indices: grid_indices(width, height),
         uvs,
-        heights, // used for heightfield collision!
+        heights, // used for physics colliders!
                  // TODO add normals when flat shading won't cut it no more!
     }
 }
 
+/// Triangle indices of a row-major grid of `width` by `height` vertices, shared by chunk meshes
+/// and trimesh colliders.
+pub fn grid_indices(width: usize, height: usize) -> Vec<u32> {
+    let mut indices = Vec::with_capacity((height - 1) * (width - 1) * 6);
+    let row = width as u32;
+
+    for y in 0..height - 1 {
+        for x in 0..width - 1 {
+            let i = (y * width + x) as u32;
+            indices.extend([i, i + row + 1, i + row, i + row + 1, i, i + 1]);
+        }
+    }
+
+    indices
+}
+
+/// Chunk texture for the current `TextureMode`, without touching the mesh.
+pub fn get_texture(map: &NoiseMap, density: Option<&NoiseMap>, mesh_config: &MeshConfig) -> Image {
+    let size = map.size().0 as u32;
+
+    let texture_data = to_texture_vec(map, density, mesh_config);
+
+    Image::new_fill(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        texture_data.as_slice(),
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+fn to_texture_vec(map: &NoiseMap, density: Option<&NoiseMap>, mesh_config: &MeshConfig) -> Vec<u8> {
+    match (mesh_config.texture_mode, density) {
+        (TextureMode::Color, _) => to_color_vec(map, &mesh_config.color_config),
+        (TextureMode::HeightMap(color), _) => to_heightmap_vec(map, color),
+        (TextureMode::Density(_), Some(density)) => to_heightmap_vec(density, Color::WHITE),
+        // NOTE falls back to terrain colors when the density layer does not exist.
+        (TextureMode::Density(_), None) => to_color_vec(map, &mesh_config.color_config),
+    }
+}
+
 fn to_heightmap_vec(map: &NoiseMap, base_color: Color) -> Vec<u8> {
     let size = map.size().0;
     let mut data: Vec<u8> = Vec::with_capacity(size * size);
@@ -264,19 +338,9 @@ fn to_color_vec(map: &NoiseMap, config: &ColorConfig) -> Vec<u8> {
     let mut data: Vec<u8> = Vec::with_capacity(size * size);
 
     for value in map.values() {
-        let value = (value * 0.5 + 0.5).clamp(0.0, 1.0);
-
-        let mut colors = config.colors.clone();
-        colors.sort_by(|a, b| b.start_height.total_cmp(&a.start_height));
-
-        let color_range = colors
-            .iter()
-            .find(|color| value > color.start_height as f64);
-
-        let color = match color_range {
-            Some(color_range) => color_range.color,
-            None => Color::rgb_u8(255, 0, 255),
-        };
+        let color = config
+            .color_at(value)
+            .unwrap_or_else(|| Color::rgb_u8(255, 0, 255));
 
         data.push((color.r() * 255.0) as u8); //r
         data.push((color.g() * 255.0) as u8); //g
@@ -1,8 +1,11 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
-use noise::{Fbm, NoiseFn, Perlin};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
 use rayon::prelude::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
 
+/// Raw noise values of a chunk, kept on the chunk so textures can be re-baked without
+/// sampling the noise again.
+#[derive(Component, Clone)]
 pub struct NoiseMap {
     size: usize,
     values: Vec<f64>,
@@ -35,13 +38,52 @@ impl Default for NoiseConfig {
     }
 }
 
+impl NoiseConfig {
+    // NOTE the setters rebuild the octave sources, which `Fbm::new` only makes six of.
+    pub fn fbm(&self) -> Fbm<Perlin> {
+        Fbm::new(self.seed)
+            .set_octaves(self.octaves)
+            .set_frequency(self.frequency)
+            .set_lacunarity(self.lacunarity)
+            .set_persistence(self.persistence)
+    }
+}
+
+/// Noise layers controlling object placement probability, referenced by index from spawn rules.
+#[derive(Resource, Inspectable, Clone, Default)]
+pub struct DensityLayers {
+    pub layers: Vec<NoiseConfig>,
+}
+
+pub struct DensitySampler {
+    fbm: Fbm<Perlin>,
+    offset: Vec2,
+}
+
+impl DensitySampler {
+    pub fn new(config: &NoiseConfig) -> Self {
+        Self {
+            fbm: config.fbm(),
+            offset: config.offset,
+        }
+    }
+
+    /// Placement probability in `0.0..=1.0` at the same coordinates `NoiseMap` samples,
+    /// where one chunk spans one unit.
+    pub fn get(&self, point: Vec2) -> f64 {
+        let point = point + self.offset;
+        let value = self.fbm.get([point.x as f64, point.y as f64]);
+        (value * 0.5 + 0.5).clamp(0.0, 1.0)
+    }
+}
+
 impl NoiseMap {
     pub fn new(
         fbm: &Fbm<Perlin>,
         size: usize,
         coord: (i32, i32),
         offset: Vec2,
-        use_falloff: bool,  // TODO this causes seam tearing between chunks!
+        use_falloff: bool, // TODO this causes seam tearing between chunks!
     ) -> NoiseMap {
         let mut values = vec![0.0; size * size];
 
@@ -76,6 +118,12 @@ impl NoiseMap {
         NoiseMap { size, values }
     }
 
+    /// Noise map of `size` by `size` values sampled earlier, in row-major order.
+    pub fn from_values(size: usize, values: Vec<f64>) -> NoiseMap {
+        assert_eq!(values.len(), size * size);
+        NoiseMap { size, values }
+    }
+
     pub fn get_value(&self, x: usize, y: usize) -> f32 {
         self.values[y * self.size + x] as f32
     }
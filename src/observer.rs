@@ -0,0 +1,201 @@
+use std::f32::consts::{FRAC_PI_4, FRAC_PI_8};
+
+use bevy::prelude::*;
+
+use super::{mesh::MeshConfig, world_to_chunk, Chunk, ChunkDistance, PlayerChunk};
+
+/// Keeps terrain generated within `chunk_distance` chunks around the entity.
+#[derive(Component, Clone, Copy)]
+pub struct TerrainObserver {
+    pub chunk_distance: i32,
+    pub shape: LoadingShape,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum LoadingShape {
+    /// Every chunk within `chunk_distance` along both axes.
+    #[default]
+    Square,
+    /// Every chunk within `chunk_distance` of the observer, about 20% fewer than `Square`.
+    Circle,
+    /// A `Circle` cut down to a cone of `fov` radians in front of the observer, plus every
+    /// chunk within `behind` chunks of it, which always includes its own chunk.
+    Frustum { fov: f32, behind: i32 },
+}
+
+/// Chunk and heading a [`TerrainObserver`] is currently in.
+#[derive(Component)]
+pub(crate) struct ObserverChunk {
+    chunk: (i32, i32),
+    /// Facing in chunk space, rounded to one of eight directions.
+    heading: i32,
+}
+
+/// Area of chunks kept around a single observer.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct ObservedArea {
+    pub chunk: (i32, i32),
+    pub chunk_distance: i32,
+    shape: LoadingShape,
+    heading: i32,
+}
+
+impl ObservedArea {
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        let offset = Vec2::new((x - self.chunk.0) as f32, (y - self.chunk.1) as f32);
+        // NOTE the half chunk keeps the chunks straight ahead at `chunk_distance` in the circle.
+        let radius = self.chunk_distance as f32 + 0.5;
+
+        match self.shape {
+            LoadingShape::Square => {
+                offset.x.abs() <= self.chunk_distance as f32
+                    && offset.y.abs() <= self.chunk_distance as f32
+            }
+            LoadingShape::Circle => offset.length() <= radius,
+            LoadingShape::Frustum { fov, behind } => {
+                if offset.length() <= behind.max(0) as f32 + 0.5 {
+                    return true;
+                }
+
+                // NOTE widened by half a heading step, as headings are rounded to 45 degrees.
+                offset.length() <= radius
+                    && offset.angle_between(self.heading()).abs() <= fov / 2.0 + FRAC_PI_8
+            }
+        }
+    }
+
+    /// Generation priority of a chunk, lower is sooner. `Frustum` observers rank chunks behind
+    /// them up to twice as far away as chunks in front.
+    pub fn priority(&self, (x, y): (i32, i32)) -> f32 {
+        let offset = Vec2::new((x - self.chunk.0) as f32, (y - self.chunk.1) as f32);
+
+        match self.shape {
+            LoadingShape::Frustum { .. } => {
+                let facing = offset.normalize_or_zero().dot(self.heading());
+                offset.length() * (1.5 - 0.5 * facing)
+            }
+            _ => offset.length(),
+        }
+    }
+
+    fn heading(&self) -> Vec2 {
+        let angle = self.heading as f32 * FRAC_PI_4;
+        Vec2::new(angle.cos(), angle.sin())
+    }
+}
+
+/// Areas around every observer, including the player moved by `PlayerPositionChangedEvent`.
+#[derive(Resource, Default)]
+pub(crate) struct ObserverChunks(pub Vec<ObservedArea>);
+
+impl ObserverChunks {
+    pub fn within(&self, chunk: &Chunk, distance: i32) -> bool {
+        self.0.iter().any(|area| chunk.within(area.chunk, distance))
+    }
+
+    /// Priority of a chunk for the observer that wants it soonest.
+    pub fn priority(&self, chunk: (i32, i32)) -> f32 {
+        self.0
+            .iter()
+            .map(|area| area.priority(chunk))
+            .fold(f32::MAX, f32::min)
+    }
+}
+
+pub(crate) fn track_observers(
+    mut commands: Commands,
+    mesh_config: Res<MeshConfig>,
+    stale: Query<Entity, (With<ObserverChunk>, Without<TerrainObserver>)>,
+    mut observers: Query<(
+        Entity,
+        &TerrainObserver,
+        ChangeTrackers<TerrainObserver>,
+        ChangeTrackers<GlobalTransform>,
+        &GlobalTransform,
+        Option<&mut ObserverChunk>,
+    )>,
+) {
+    // NOTE otherwise an observer added back without moving would keep its old chunk.
+    for entity in &stale {
+        commands.entity(entity).remove::<ObserverChunk>();
+    }
+
+    let scale = mesh_config.sanitized().scale;
+    for (entity, observer, observer_tracker, tracker, transform, observer_chunk) in &mut observers {
+        if !tracker.is_changed()
+            && !observer_tracker.is_changed()
+            && !mesh_config.is_changed()
+            && observer_chunk.is_some()
+        {
+            continue;
+        }
+
+        let chunk = world_to_chunk(transform.translation(), scale);
+        // NOTE only frustums depend on the heading, so turning doesn't rebuild the pool otherwise.
+        let heading = match observer.shape {
+            LoadingShape::Frustum { .. } => {
+                let forward = transform.forward();
+                // NOTE chunk y grows towards world -z.
+                ((-forward.z).atan2(forward.x) / FRAC_PI_4)
+                    .round()
+                    .rem_euclid(8.0) as i32
+            }
+            _ => 0,
+        };
+
+        match observer_chunk {
+            Some(mut observer_chunk) => {
+                // NOTE only assigned on a new chunk or heading, to keep change detection quiet.
+                if observer_chunk.chunk != chunk || observer_chunk.heading != heading {
+                    *observer_chunk = ObserverChunk { chunk, heading };
+                }
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(ObserverChunk { chunk, heading });
+            }
+        }
+    }
+}
+
+pub(crate) fn update_observer_chunks(
+    mut observer_chunks: ResMut<ObserverChunks>,
+    player_chunk: Res<PlayerChunk>,
+    chunk_distance: Res<ChunkDistance>,
+    observers: Query<(&TerrainObserver, &ObserverChunk)>,
+    changed: Query<(), Or<(Changed<TerrainObserver>, Changed<ObserverChunk>)>>,
+    stale: Query<(), (With<ObserverChunk>, Without<TerrainObserver>)>,
+    removed: RemovedComponents<TerrainObserver>,
+) {
+    if !player_chunk.is_changed()
+        && changed.is_empty()
+        && stale.is_empty()
+        && removed.iter().next().is_none()
+    {
+        return;
+    }
+
+    let player = player_chunk.0.map(|chunk| ObservedArea {
+        chunk,
+        chunk_distance: chunk_distance.0,
+        shape: LoadingShape::Square,
+        heading: 0,
+    });
+    let mut areas: Vec<ObservedArea> = player.into_iter().collect();
+    areas.extend(
+        observers
+            .iter()
+            .map(|(observer, observer_chunk)| ObservedArea {
+                chunk: observer_chunk.chunk,
+                chunk_distance: observer.chunk_distance,
+                shape: observer.shape,
+                heading: observer_chunk.heading,
+            }),
+    );
+
+    // NOTE only assigned on change, so dependent systems can skip unchanged frames.
+    if observer_chunks.0 != areas {
+        observer_chunks.0 = areas;
+    }
+}
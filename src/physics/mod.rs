@@ -0,0 +1,285 @@
+#[cfg(feature = "rapier")]
+mod rapier;
+
+use std::marker::PhantomData;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use super::{mesh::MeshConfig, world_to_chunk, Chunk, ObserverChunks, PhysicsDistance};
+
+#[cfg(feature = "rapier")]
+pub use self::rapier::RapierBackend;
+
+/// Height samples of a generated chunk, laid out like the vertices of the chunk mesh.
+#[derive(Component, Clone)]
+pub struct ChunkHeights {
+    /// Number of samples along each side of the chunk.
+    pub size: usize,
+    /// Side length of the chunk in world units.
+    pub scale: f32,
+    /// Heights in world units, row by row from the chunk's +z edge towards its -z edge.
+    pub heights: Vec<f32>,
+}
+
+impl ChunkHeights {
+    /// Chunk-local position of the sample in column `x` and row `y`.
+    pub fn position(&self, x: usize, y: usize) -> Vec3 {
+        let step = self.scale / (self.size - 1) as f32;
+        Vec3::new(
+            x as f32 * step - self.scale / 2.0,
+            self.heights[y * self.size + x],
+            self.scale / 2.0 - y as f32 * step,
+        )
+    }
+}
+
+#[derive(Clone)]
+pub enum ColliderShape {
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+    Cuboid {
+        half_extents: Vec3,
+    },
+    /// Convex hull of the mesh at the given asset path.
+    ConvexHull(String),
+}
+
+/// Collider a physics backend attaches to a spawned object while its chunk is within
+/// [`PhysicsDistance`]. Objects are placed at their base, so primitives should stand on it.
+#[derive(Component, Clone)]
+pub enum ObjectCollider {
+    Capsule { half_height: f32, radius: f32 },
+    Cuboid { half_extents: Vec3 },
+    ConvexHull(Handle<Mesh>),
+}
+
+impl ObjectCollider {
+    pub(crate) fn new(shape: &ColliderShape, assets: &AssetServer) -> Self {
+        match shape {
+            ColliderShape::Capsule {
+                half_height,
+                radius,
+            } => Self::Capsule {
+                half_height: *half_height,
+                radius: *radius,
+            },
+            ColliderShape::Cuboid { half_extents } => Self::Cuboid {
+                half_extents: *half_extents,
+            },
+            ColliderShape::ConvexHull(mesh) => Self::ConvexHull(assets.load(mesh.as_str())),
+        }
+    }
+}
+
+/// Keeps colliders loaded around the entity, in addition to around terrain observers.
+#[derive(Component, Default)]
+pub struct PhysicsActor;
+
+/// Chunks containing a terrain observer or a [`PhysicsActor`].
+#[derive(Resource, Default)]
+struct ActorChunks(Vec<(i32, i32)>);
+
+impl ActorChunks {
+    fn in_range(&self, chunk: &Chunk, distance: i32) -> bool {
+        self.0.iter().any(|actor| chunk.within(*actor, distance))
+    }
+}
+
+/// Collider entity spawned for a chunk.
+#[derive(Component)]
+pub(crate) struct ChunkCollider(pub Entity);
+
+/// Builds colliders for a physics engine from the data Ymir generates.
+pub trait PhysicsBackend: Send + Sync + 'static {
+    type Collider: Component + Clone;
+
+    /// Collider of a chunk and its transform relative to the chunk, if it should have one.
+    fn chunk_collider(
+        heights: &ChunkHeights,
+        mesh_config: &MeshConfig,
+    ) -> Option<(Self::Collider, Transform)>;
+
+    /// Collider of a spawned object, or `None` if it cannot be built yet.
+    fn object_collider(collider: &ObjectCollider, meshes: &Assets<Mesh>) -> Option<Self::Collider>;
+}
+
+/// Adds colliders built by `B` to chunks and spawned objects.
+pub struct PhysicsPlugin<B: PhysicsBackend>(PhantomData<B>);
+
+impl<B: PhysicsBackend> Default for PhysicsPlugin<B> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<B: PhysicsBackend> Plugin for PhysicsPlugin<B> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActorChunks>()
+            .add_system(register_physics_actors)
+            .add_system(update_chunk_colliders::<B>)
+            .add_system(update_object_colliders::<B>);
+    }
+}
+
+fn register_physics_actors(
+    mut actor_chunks: ResMut<ActorChunks>,
+    observer_chunks: Res<ObserverChunks>,
+    mesh_config: Res<MeshConfig>,
+    actors: Query<&GlobalTransform, With<PhysicsActor>>,
+) {
+    let scale = mesh_config.sanitized().scale;
+    let observers = observer_chunks.0.iter().map(|area| area.chunk);
+    let actors = actors
+        .iter()
+        .map(|transform| world_to_chunk(transform.translation(), scale));
+
+    let mut chunks = vec![];
+    for chunk in observers.chain(actors) {
+        if !chunks.contains(&chunk) {
+            chunks.push(chunk);
+        }
+    }
+
+    // NOTE compared first, so colliders are only revisited when an actor crosses a chunk border.
+    if actor_chunks.0 != chunks {
+        actor_chunks.0 = chunks;
+    }
+}
+
+fn update_chunk_colliders<B: PhysicsBackend>(
+    mut commands: Commands,
+    mesh_config: Res<MeshConfig>,
+    actor_chunks: Res<ActorChunks>,
+    physics_distance: Res<PhysicsDistance>,
+    chunks: Query<(
+        Entity,
+        &Chunk,
+        ChangeTrackers<ChunkHeights>,
+        &ChunkHeights,
+        Option<&ChunkCollider>,
+    )>,
+    changed: Query<(), Changed<ChunkHeights>>,
+) {
+    if !actor_chunks.is_changed() && changed.is_empty() {
+        return;
+    }
+
+    for (entity, chunk, tracker, heights, chunk_collider) in &chunks {
+        let in_range = actor_chunks.in_range(chunk, physics_distance.0);
+        // NOTE heights change when the chunk is edited in place.
+        let outdated = chunk_collider.is_some() && tracker.is_changed();
+
+        if let Some(ChunkCollider(collider)) = chunk_collider {
+            if !in_range || outdated {
+                commands.entity(*collider).despawn_recursive();
+                commands.entity(entity).remove::<ChunkCollider>();
+            }
+        }
+
+        if in_range && (chunk_collider.is_none() || outdated) {
+            let Some((collider, transform)) = B::chunk_collider(heights, &mesh_config) else {
+                continue;
+            };
+            let collider = commands
+                .spawn((TransformBundle::from_transform(transform), collider))
+                .id();
+            commands
+                .entity(entity)
+                .add_child(collider)
+                .insert(ChunkCollider(collider));
+        }
+    }
+}
+
+/// Marks spawned objects whose collider could not be built, so they are not retried.
+#[derive(Component)]
+struct FailedCollider;
+
+#[allow(clippy::too_many_arguments)]
+fn update_object_colliders<B: PhysicsBackend>(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    actor_chunks: Res<ActorChunks>,
+    physics_distance: Res<PhysicsDistance>,
+    chunks: Query<&Chunk>,
+    objects: Query<
+        (
+            Entity,
+            &ObjectCollider,
+            ChangeTrackers<ObjectCollider>,
+            &Parent,
+            Option<&B::Collider>,
+        ),
+        Without<FailedCollider>,
+    >,
+    added: Query<(), Added<ObjectCollider>>,
+    mut hulls: Local<HashMap<Handle<Mesh>, Option<B::Collider>>>,
+) {
+    let loaded: HashSet<Handle<Mesh>> = mesh_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                Some(handle.clone())
+            }
+            AssetEvent::Removed { .. } => None,
+        })
+        .collect();
+    for handle in &loaded {
+        hulls.remove(handle);
+    }
+    if !actor_chunks.is_changed() && added.is_empty() && loaded.is_empty() {
+        return;
+    }
+
+    for (entity, object_collider, tracker, parent, collider) in &objects {
+        // NOTE objects waiting on a convex hull mesh are only retried once it loads.
+        let waiting = matches!(object_collider,
+            ObjectCollider::ConvexHull(handle) if !loaded.contains(handle));
+        if !actor_chunks.is_changed() && !tracker.is_added() && waiting {
+            continue;
+        }
+        let Ok(chunk) = chunks.get(parent.get()) else {
+            continue;
+        };
+        let in_range = actor_chunks.in_range(chunk, physics_distance.0);
+
+        match (in_range, collider) {
+            (true, None) => {
+                let collider = match object_collider {
+                    ObjectCollider::ConvexHull(handle) => match hulls.get(handle) {
+                        Some(hull) => hull.clone(),
+                        None => {
+                            let hull = B::object_collider(object_collider, &meshes);
+                            // NOTE failed hulls are cached too, once their mesh has loaded.
+                            if meshes.contains(handle) {
+                                hulls.insert(handle.clone(), hull.clone());
+                            }
+                            hull
+                        }
+                    },
+                    _ => B::object_collider(object_collider, &meshes),
+                };
+
+                match (collider, object_collider) {
+                    (Some(collider), _) => {
+                        commands.entity(entity).insert(collider);
+                    }
+                    (None, ObjectCollider::ConvexHull(handle)) if !meshes.contains(handle) => {}
+                    (None, _) => {
+                        commands.entity(entity).insert(FailedCollider);
+                    }
+                }
+            }
+            (false, Some(_)) => {
+                commands.entity(entity).remove::<B::Collider>();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+use bevy_rapier3d::prelude::*;
+
+use super::{ChunkHeights, ObjectCollider, PhysicsBackend};
+use crate::mesh::{grid_indices, ColliderMode, MeshConfig, RenderMode};
+
+pub struct RapierBackend;
+
+impl PhysicsBackend for RapierBackend {
+    type Collider = Collider;
+
+    fn chunk_collider(
+        heights: &ChunkHeights,
+        mesh_config: &MeshConfig,
+    ) -> Option<(Collider, Transform)> {
+        let size = heights.size;
+        match (mesh_config.render_mode, mesh_config.collider_mode) {
+            (_, ColliderMode::None) => None,
+            // NOTE the top face of the cuboid lines up with the plane.
+            (RenderMode::Plane, _) => Some((
+                Collider::cuboid(heights.scale / 2.0, 0.5, heights.scale / 2.0),
+                Transform::from_xyz(0.0, -0.5, 0.0),
+            )),
+            (RenderMode::Mesh, ColliderMode::Heightfield) => {
+                // NOTE parry expects heights column-major with rows along +z and columns along +x,
+                // centered on the origin and split along the same cell diagonals as the mesh.
+                let mut columns = Vec::with_capacity(size * size);
+                for x in 0..size {
+                    for row in 0..size {
+                        columns.push(heights.position(x, size - 1 - row).y);
+                    }
+                }
+
+                let collider = Collider::heightfield(
+                    columns,
+                    size,
+                    size,
+                    Vec3::new(heights.scale, 1.0, heights.scale),
+                );
+
+                Some((collider, Transform::IDENTITY))
+            }
+            (RenderMode::Mesh, ColliderMode::Trimesh) => {
+                let mut vertices = Vec::with_capacity(size * size);
+                for y in 0..size {
+                    for x in 0..size {
+                        vertices.push(heights.position(x, y));
+                    }
+                }
+                let indices = grid_indices(size, size)
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                    .collect();
+
+                Some((Collider::trimesh(vertices, indices), Transform::IDENTITY))
+            }
+        }
+    }
+
+    fn object_collider(collider: &ObjectCollider, meshes: &Assets<Mesh>) -> Option<Collider> {
+        match collider {
+            ObjectCollider::Capsule {
+                half_height,
+                radius,
+            } => Some(Collider::compound(vec![(
+                Vec3::Y * (half_height + radius),
+                Quat::IDENTITY,
+                Collider::capsule_y(*half_height, *radius),
+            )])),
+            ObjectCollider::Cuboid { half_extents } => Some(Collider::compound(vec![(
+                Vec3::Y * half_extents.y,
+                Quat::IDENTITY,
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            )])),
+            ObjectCollider::ConvexHull(handle) => {
+                match meshes.get(handle)?.attribute(Mesh::ATTRIBUTE_POSITION) {
+                    Some(VertexAttributeValues::Float32x3(positions)) => {
+                        let points: Vec<Vec3> =
+                            positions.iter().map(|pos| Vec3::from(*pos)).collect();
+                        Collider::convex_hull(&points)
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+}
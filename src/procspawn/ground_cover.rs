@@ -0,0 +1,153 @@
+use std::{f32::consts::TAU, ops::RangeInclusive};
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{mesh::VertexAttributeValues, view::NoFrustumCulling},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    instancing::{InstanceData, InstancedObjects},
+    Chunk, GroundCoverDistance, MeshConfig, ObserverChunks,
+};
+
+#[derive(Clone)]
+pub struct GroundCoverRule {
+    /// Asset path of the instanced mesh, e.g. `models/grass.glb#Mesh0/Primitive0`.
+    pub mesh: String,
+    pub color: Color,
+    /// Index into `ColorConfig::colors` of the band this rule covers.
+    pub band: usize,
+    /// Instances per square unit of terrain surface.
+    pub density: f32,
+    pub scale: RangeInclusive<f32>,
+}
+
+#[derive(Resource, Clone, Default)]
+pub struct GroundCoverRules(pub Vec<GroundCoverRule>);
+
+#[derive(Component)]
+struct GroundCovered;
+
+#[derive(Component)]
+struct GroundCover;
+
+pub(super) fn spawn_ground_cover(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mesh_config: Res<MeshConfig>,
+    rules: Res<GroundCoverRules>,
+    assets: Res<AssetServer>,
+    observer_chunks: Res<ObserverChunks>,
+    ground_cover_distance: Res<GroundCoverDistance>,
+    query: Query<(Entity, &Handle<Mesh>, &Chunk), Without<GroundCovered>>,
+) {
+    if rules.0.is_empty() {
+        return;
+    }
+
+    for (entity, mesh_handle, chunk) in &query {
+        if !observer_chunks.within(chunk, ground_cover_distance.0) {
+            continue;
+        }
+        let Some(mesh) = meshes.get(mesh_handle) else {
+            continue;
+        };
+
+        commands.entity(entity).with_children(|children| {
+            for (i, rule) in rules.0.iter().enumerate() {
+                // NOTE seeded per chunk so cover looks the same every time the player returns.
+                let seed = (((chunk.x as u64) << 32) | chunk.y as u32 as u64) ^ i as u64;
+                let mut rng = StdRng::seed_from_u64(seed);
+                let instances = scatter(mesh, rule, &mesh_config, &mut rng)
+                    .iter()
+                    .map(|transform| InstanceData::new(transform, rule.color))
+                    .collect();
+
+                children.spawn((
+                    assets.load::<Mesh, _>(rule.mesh.as_str()),
+                    SpatialBundle::VISIBLE_IDENTITY,
+                    InstancedObjects(instances),
+                    NoFrustumCulling,
+                    NotShadowCaster,
+                    GroundCover,
+                ));
+            }
+        });
+        commands.entity(entity).insert(GroundCovered);
+    }
+}
+
+pub(super) fn despawn_ground_cover(
+    mut commands: Commands,
+    observer_chunks: Res<ObserverChunks>,
+    ground_cover_distance: Res<GroundCoverDistance>,
+    chunks: Query<(Entity, &Chunk, Option<&Children>), With<GroundCovered>>,
+    cover: Query<Entity, With<GroundCover>>,
+) {
+    if !observer_chunks.is_changed() && !ground_cover_distance.is_changed() {
+        return;
+    }
+
+    for (entity, chunk, children) in &chunks {
+        if observer_chunks.within(chunk, ground_cover_distance.0) {
+            continue;
+        }
+
+        let children = children.map_or(&[][..], |children| &**children);
+        for cover in cover.iter_many(children) {
+            commands.entity(cover).despawn_recursive();
+        }
+        commands.entity(entity).remove::<GroundCovered>();
+    }
+}
+
+fn scatter(
+    mesh: &Mesh,
+    rule: &GroundCoverRule,
+    mesh_config: &MeshConfig,
+    rng: &mut impl Rng,
+) -> Vec<Transform> {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return vec![];
+    };
+    // NOTE flat shaded meshes have their indices consumed by `Mesh::duplicate_vertices`.
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(indices) => indices.iter().collect(),
+        None => (0..positions.len()).collect(),
+    };
+
+    let mut transforms = vec![];
+    for triangle in indices.chunks_exact(3) {
+        let a = Vec3::from(positions[triangle[0]]);
+        let b = Vec3::from(positions[triangle[1]]);
+        let c = Vec3::from(positions[triangle[2]]);
+
+        let expected = (b - a).cross(c - a).length() * 0.5 * rule.density;
+        let count = expected as usize + usize::from(rng.gen::<f32>() < expected.fract());
+
+        for _ in 0..count {
+            let (mut u, mut v) = (rng.gen::<f32>(), rng.gen::<f32>());
+            if u + v > 1.0 {
+                u = 1.0 - u;
+                v = 1.0 - v;
+            }
+            let point = a + (b - a) * u + (c - a) * v;
+
+            let value = point.y / mesh_config.height_multiplier;
+            if mesh_config.color_config.band_at(value as f64) != Some(rule.band) {
+                continue;
+            }
+
+            let mut transform = Transform::from_translation(point);
+            transform.rotation = Quat::from_rotation_y(rng.gen_range(0.0..TAU));
+            transform.scale = Vec3::splat(rng.gen_range(rule.scale.clone()));
+            transforms.push(transform);
+        }
+    }
+
+    transforms
+}
@@ -0,0 +1,275 @@
+use bevy::{
+    asset::load_internal_asset,
+    core::{cast_slice, Pod, Zeroable},
+    core_pipeline::core_3d::Opaque3d,
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+            SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        view::ExtractedView,
+        Extract, RenderApp, RenderStage,
+    },
+    utils::{HashMap, HashSet},
+};
+
+const INSTANCING_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 5186712356203465231);
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct InstanceData {
+    transform: Mat4,
+    color: [f32; 4],
+}
+
+// SAFETY: `InstanceData` is `repr(C)` and only made of `f32`s, so it has no padding.
+unsafe impl Zeroable for InstanceData {}
+unsafe impl Pod for InstanceData {}
+
+impl InstanceData {
+    pub fn new(transform: &Transform, color: Color) -> Self {
+        Self {
+            transform: transform.compute_matrix(),
+            color: color.as_linear_rgba_f32(),
+        }
+    }
+}
+
+/// Draws the entity's mesh once per instance, with instance transforms relative to the entity.
+#[derive(Component, Deref)]
+pub struct InstancedObjects(pub Vec<InstanceData>);
+
+/// Render world copy of [`InstancedObjects`], holding the instances only in frames they changed.
+#[derive(Component)]
+struct ExtractedInstances(Option<Vec<InstanceData>>);
+
+pub struct InstancingPlugin;
+
+impl Plugin for InstancingPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            INSTANCING_SHADER_HANDLE,
+            "instancing.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawInstanced>()
+            .init_resource::<InstancingPipeline>()
+            .init_resource::<SpecializedMeshPipelines<InstancingPipeline>>()
+            .init_resource::<InstanceBuffers>()
+            .add_system_to_stage(RenderStage::Extract, extract_instanced_objects)
+            .add_system_to_stage(RenderStage::Queue, queue_instanced)
+            .add_system_to_stage(RenderStage::Prepare, prepare_instance_buffers);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_instanced(
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    instancing_pipeline: Res<InstancingPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<InstancingPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    instanced_meshes: Query<(Entity, &MeshUniform, &Handle<Mesh>), With<ExtractedInstances>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Opaque3d>)>,
+) {
+    let draw_instanced = opaque_3d_draw_functions
+        .read()
+        .get_id::<DrawInstanced>()
+        .unwrap();
+
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples);
+
+    for (view, mut opaque_phase) in &mut views {
+        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+        for (entity, mesh_uniform, mesh_handle) in &instanced_meshes {
+            let Some(mesh) = meshes.get(mesh_handle) else {
+                continue;
+            };
+            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            let pipeline = match pipelines.specialize(
+                &mut pipeline_cache,
+                &instancing_pipeline,
+                key,
+                &mesh.layout,
+            ) {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    error!("{}", err);
+                    continue;
+                }
+            };
+            opaque_phase.add(Opaque3d {
+                entity,
+                pipeline,
+                draw_function: draw_instanced,
+                distance: rangefinder.distance(&mesh_uniform.transform),
+            });
+        }
+    }
+}
+
+fn extract_instanced_objects(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    query: Extract<Query<(Entity, &InstancedObjects, ChangeTrackers<InstancedObjects>)>>,
+) {
+    let mut values = Vec::with_capacity(*previous_len);
+    for (entity, instances, tracker) in &query {
+        let instances = tracker.is_changed().then(|| instances.0.clone());
+        values.push((entity, ExtractedInstances(instances)));
+    }
+    *previous_len = values.len();
+    commands.insert_or_spawn_batch(values);
+}
+
+struct InstanceBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+/// Instance buffers by entity, kept across frames since the render world entities are not.
+#[derive(Resource, Default)]
+struct InstanceBuffers(HashMap<Entity, InstanceBuffer>);
+
+fn prepare_instance_buffers(
+    query: Query<(Entity, &ExtractedInstances)>,
+    render_device: Res<RenderDevice>,
+    mut buffers: ResMut<InstanceBuffers>,
+) {
+    for (entity, extracted) in &query {
+        let Some(instances) = &extracted.0 else {
+            continue;
+        };
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("ymir instance buffer"),
+            contents: cast_slice(instances.as_slice()),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        buffers.0.insert(
+            entity,
+            InstanceBuffer {
+                buffer,
+                length: instances.len(),
+            },
+        );
+    }
+
+    let extracted: HashSet<Entity> = query.iter().map(|(entity, _)| entity).collect();
+    buffers.0.retain(|entity, _| extracted.contains(entity));
+}
+
+#[derive(Resource)]
+struct InstancingPipeline {
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for InstancingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            mesh_pipeline: world.resource::<MeshPipeline>().clone(),
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for InstancingPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+        let shader = INSTANCING_SHADER_HANDLE.typed::<Shader>();
+
+        // NOTE locations 0-7 are left to the regular mesh attributes.
+        let attributes = (0..5)
+            .map(|i| VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: i * VertexFormat::Float32x4.size(),
+                shader_location: 8 + i as u32,
+            })
+            .collect();
+
+        descriptor.vertex.shader = shader.clone();
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceData>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes,
+        });
+        descriptor.fragment.as_mut().unwrap().shader = shader;
+        descriptor.layout = Some(vec![
+            self.mesh_pipeline.view_layout.clone(),
+            self.mesh_pipeline.mesh_layout.clone(),
+        ]);
+
+        Ok(descriptor)
+    }
+}
+
+type DrawInstanced = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawMeshInstanced,
+);
+
+struct DrawMeshInstanced;
+
+impl EntityRenderCommand for DrawMeshInstanced {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SQuery<Read<Handle<Mesh>>>,
+        SRes<InstanceBuffers>,
+    );
+
+    #[inline]
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, mesh_query, instance_buffers): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Ok(mesh_handle) = mesh_query.get(item) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(instance_buffer) = instance_buffers.into_inner().0.get(&item) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(gpu_mesh) = meshes.into_inner().get(mesh_handle) else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..instance_buffer.length as u32);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, 0..instance_buffer.length as u32);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
@@ -0,0 +1,47 @@
+#import bevy_pbr::mesh_types
+#import bevy_pbr::mesh_view_bindings
+
+@group(1) @binding(0)
+var<uniform> mesh: Mesh;
+
+// NOTE: Bindings must come before functions that use them!
+#import bevy_pbr::mesh_functions
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+
+    @location(8) i_model_0: vec4<f32>,
+    @location(9) i_model_1: vec4<f32>,
+    @location(10) i_model_2: vec4<f32>,
+    @location(11) i_model_3: vec4<f32>,
+    @location(12) i_color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let instance = mat4x4<f32>(vertex.i_model_0, vertex.i_model_1, vertex.i_model_2, vertex.i_model_3);
+    let model = mesh.model * instance;
+
+    var out: VertexOutput;
+    out.clip_position = mesh_position_local_to_clip(model, vec4<f32>(vertex.position, 1.0));
+    out.world_normal = normalize((model * vec4<f32>(vertex.normal, 0.0)).xyz);
+    out.color = vertex.i_color;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    var diffuse = 0.0;
+    for (var i: u32 = 0u; i < lights.n_directional_lights; i = i + 1u) {
+        diffuse = diffuse + max(dot(in.world_normal, lights.directional_lights[i].direction_to_light), 0.0);
+    }
+    let light = clamp(lights.ambient_color.rgb + vec3<f32>(diffuse), vec3<f32>(0.0), vec3<f32>(1.0));
+    return vec4<f32>(in.color.rgb * light, in.color.a);
+}
@@ -1,44 +1,112 @@
-use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+mod ground_cover;
+mod instancing;
+
+use std::ops::RangeInclusive;
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{mesh::VertexAttributeValues, view::NoFrustumCulling},
+};
 use rand::Rng;
 
-use super::{mesh::MeshConfig, Chunk, ObjectDistance, PlayerChunk};
+use self::{
+    ground_cover::{despawn_ground_cover, spawn_ground_cover},
+    instancing::{InstanceData, InstancedObjects, InstancingPlugin},
+};
+use super::{
+    mesh::MeshConfig,
+    noise::{DensityLayers, DensitySampler},
+    physics::{ColliderShape, ObjectCollider},
+    Chunk, GroundCoverDistance, ObjectDistance, ObserverChunks,
+};
+
+pub use self::ground_cover::{GroundCoverRule, GroundCoverRules};
+
+/// Extra chunks beyond [`ObjectDistance`] that keep their objects, so walking back and forth
+/// across a chunk border does not despawn and respawn everything.
+const DESPAWN_HYSTERESIS: i32 = 1;
 
 #[derive(Component)]
 struct SpawnedObjects;
 
+#[derive(Component)]
+struct SpawnedObject;
+
+#[derive(Clone)]
+pub enum SpawnMode {
+    /// One scene entity per object, for objects that need to be individually addressable.
+    Scene(String),
+    /// All objects of the rule in a chunk are drawn as instances of a single mesh in one draw call.
+    Instanced { mesh: String, color: Color },
+}
+
+#[derive(Clone)]
+pub struct SpawnRule {
+    pub mode: SpawnMode,
+    /// Lowest spawn height, relative to `MeshConfig::height_multiplier`.
+    pub min_height: f32,
+    /// Highest spawn height, relative to `MeshConfig::height_multiplier`.
+    pub max_height: f32,
+    pub scale: RangeInclusive<f32>,
+    pub jitter: f32,
+    /// Index into [`DensityLayers`] scaling the placement probability, or `None` to place
+    /// an object at every eligible vertex.
+    pub density: Option<usize>,
+    /// Collider added to each object while its chunk is within `YmirPlugin::physics_distance`.
+    pub collider: Option<ColliderShape>,
+}
+
+#[derive(Resource, Clone)]
+pub struct SpawnRules(pub Vec<SpawnRule>);
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self(vec![SpawnRule {
+            mode: SpawnMode::Scene("models/tree.glb#Scene0".into()),
+            min_height: -0.1,
+            max_height: 0.35,
+            scale: 1.4..=2.4,
+            jitter: 10.0,
+            density: None,
+            collider: None,
+        }])
+    }
+}
+
 #[derive(Default)]
 pub struct ProcSpawnPlugin;
 
 impl Plugin for ProcSpawnPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(_dummy_spawn_points); // TODO this needs a severe revamp
+        app.add_plugin(InstancingPlugin)
+            .add_system(spawn_objects)
+            .add_system(despawn_distant_objects)
+            .add_system(spawn_ground_cover)
+            .add_system(despawn_ground_cover);
     }
 }
 
-fn _dummy_spawn_points(
+#[allow(clippy::too_many_arguments)]
+fn spawn_objects(
     mut commands: Commands,
     meshes: Res<Assets<Mesh>>,
-    mesh_config: Res<MeshConfig>, // handle this differently, needs some sort of heightbased generation condition!
+    mesh_config: Res<MeshConfig>,
+    spawn_rules: Res<SpawnRules>,
+    density_layers: Res<DensityLayers>,
     assets: Res<AssetServer>,
-    player_chunk: Res<PlayerChunk>,
+    observer_chunks: Res<ObserverChunks>,
     object_distance: Res<ObjectDistance>,
     query: Query<(Entity, &Handle<Mesh>, &Chunk), (With<Chunk>, Without<SpawnedObjects>)>,
 ) {
-    if !player_chunk.is_changed() {
-        return;
-    }
-
+    let mesh_config = mesh_config.sanitized();
+    // NOTE checked every frame like ground cover, as chunks arrive over many frames.
     let chunks: Vec<(Entity, &Handle<Mesh>, &Chunk)> = query
         .into_iter()
-        .filter(|(_, _, chunk)| {
-            let (x, y) = player_chunk.0;
-
-            (x - object_distance.0..=x + object_distance.0).contains(&chunk.x)
-                && (y - object_distance.0..=y + object_distance.0).contains(&chunk.y)
-        })
+        .filter(|(_, _, chunk)| observer_chunks.within(chunk, object_distance.0))
         .collect();
 
-    for (entity, mesh_handle, _) in chunks {
+    for (entity, mesh_handle, chunk) in chunks {
         let positions = match meshes.get(mesh_handle) {
             Some(mesh) => match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
                 Some(VertexAttributeValues::Float32x3(positions)) => Some(positions.clone()),
@@ -48,46 +116,160 @@ fn _dummy_spawn_points(
         };
 
         if let Some(positions) = positions {
-            let mut vec = vec![];
-            for pos in positions {
-                if pos[1] > 0.35 * mesh_config.height_multiplier
-                    || pos[1] < -0.1 * mesh_config.height_multiplier
-                {
-                    continue;
-                }
-                let v: Vec3 = pos.into();
-                if !vec.contains(&v) {
-                    vec.push(v);
-
-                    let mut transform = Transform::from_translation(v);
-                    transform.scale = Vec3::new(
-                        rand::thread_rng().gen_range(1.4..=2.4),
-                        rand::thread_rng().gen_range(1.4..=2.4),
-                        rand::thread_rng().gen_range(1.4..=2.4),
-                    );
-                    transform.rotation = Quat::from_euler(
-                        EulerRot::XYZ,
-                        0.0,
-                        rand::thread_rng().gen_range(0.0..=360.0),
-                        0.0,
-                    );
-                    let pos_offset = Vec3::new(
-                        rand::thread_rng().gen_range(-1.0..1.0) * 10.0,
-                        0.0,
-                        rand::thread_rng().gen_range(-1.0..1.0) * 10.0,
-                    );
-                    transform.translation += pos_offset;
-
-                    commands.entity(entity).with_children(|children| {
-                        children.spawn(SceneBundle {
-                            scene: assets.load("models/tree.glb#Scene0"),
+            for rule in &spawn_rules.0 {
+                let density = rule
+                    .density
+                    .and_then(|layer| density_layers.layers.get(layer))
+                    .map(DensitySampler::new);
+                let transforms =
+                    place_objects(&positions, rule, density.as_ref(), chunk, &mesh_config);
+                spawn_rule_objects(&mut commands, entity, rule, transforms, &assets);
+            }
+        }
+        commands.entity(entity).insert(SpawnedObjects);
+    }
+}
+
+fn place_objects(
+    positions: &[[f32; 3]],
+    rule: &SpawnRule,
+    density: Option<&DensitySampler>,
+    chunk: &Chunk,
+    mesh_config: &MeshConfig,
+) -> Vec<Transform> {
+    let height_multiplier = mesh_config.height_multiplier;
+    let mut rng = rand::thread_rng();
+    let mut placed: Vec<Vec3> = vec![];
+    let mut transforms = vec![];
+
+    for pos in positions {
+        if pos[1] > rule.max_height * height_multiplier
+            || pos[1] < rule.min_height * height_multiplier
+        {
+            continue;
+        }
+        let v: Vec3 = (*pos).into();
+        if placed.contains(&v) {
+            continue;
+        }
+        placed.push(v);
+
+        let mut transform = Transform::from_translation(v);
+        transform.scale = Vec3::new(
+            rng.gen_range(rule.scale.clone()),
+            rng.gen_range(rule.scale.clone()),
+            rng.gen_range(rule.scale.clone()),
+        );
+        transform.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, rng.gen_range(0.0..=360.0), 0.0);
+        let pos_offset = Vec3::new(
+            rng.gen_range(-1.0..1.0) * rule.jitter,
+            0.0,
+            rng.gen_range(-1.0..1.0) * rule.jitter,
+        );
+        transform.translation += pos_offset;
+
+        if let Some(density) = density {
+            // NOTE maps chunk-local positions back to the coordinates `NoiseMap` samples.
+            let point = Vec2::new(
+                chunk.x as f32 + transform.translation.x / mesh_config.scale + 0.5,
+                chunk.y as f32 - transform.translation.z / mesh_config.scale + 0.5,
+            );
+            if rng.gen::<f64>() >= density.get(point) {
+                continue;
+            }
+        }
+
+        transforms.push(transform);
+    }
+
+    transforms
+}
+
+fn spawn_rule_objects(
+    commands: &mut Commands,
+    chunk: Entity,
+    rule: &SpawnRule,
+    transforms: Vec<Transform>,
+    assets: &AssetServer,
+) {
+    let collider = rule
+        .collider
+        .as_ref()
+        .map(|shape| ObjectCollider::new(shape, assets));
+
+    match &rule.mode {
+        SpawnMode::Scene(scene) => {
+            let scene: Handle<Scene> = assets.load(scene.as_str());
+            commands.entity(chunk).with_children(|children| {
+                for transform in transforms {
+                    let mut object = children.spawn((
+                        SceneBundle {
+                            scene: scene.clone(),
                             transform,
                             ..default()
-                        });
-                    });
+                        },
+                        SpawnedObject,
+                    ));
+                    if let Some(collider) = &collider {
+                        object.insert(collider.clone());
+                    }
                 }
-            }
+            });
         }
-        commands.entity(entity).insert(SpawnedObjects);
+        SpawnMode::Instanced { mesh, color } => {
+            let mesh: Handle<Mesh> = assets.load(mesh.as_str());
+            let instances = transforms
+                .iter()
+                .map(|transform| InstanceData::new(transform, *color))
+                .collect();
+            commands.entity(chunk).with_children(|children| {
+                children.spawn((
+                    mesh,
+                    SpatialBundle::VISIBLE_IDENTITY,
+                    InstancedObjects(instances),
+                    // NOTE the mesh Aabb only covers a single instance at the chunk origin.
+                    NoFrustumCulling,
+                    NotShadowCaster,
+                    SpawnedObject,
+                ));
+
+                // NOTE instances have no entities of their own, so colliders get one each.
+                if let Some(collider) = &collider {
+                    for transform in transforms {
+                        children.spawn((
+                            TransformBundle::from_transform(transform),
+                            collider.clone(),
+                            SpawnedObject,
+                        ));
+                    }
+                }
+            });
+        }
+    }
+}
+
+fn despawn_distant_objects(
+    mut commands: Commands,
+    observer_chunks: Res<ObserverChunks>,
+    object_distance: Res<ObjectDistance>,
+    chunks: Query<(Entity, &Chunk, Option<&Children>), With<SpawnedObjects>>,
+    objects: Query<Entity, With<SpawnedObject>>,
+) {
+    if !observer_chunks.is_changed() {
+        return;
+    }
+
+    let distance = object_distance.0 + DESPAWN_HYSTERESIS;
+    for (entity, chunk, children) in &chunks {
+        if observer_chunks.within(chunk, distance) {
+            continue;
+        }
+
+        // NOTE chunks left without children are still unmarked, so they spawn objects again.
+        let children = children.map_or(&[][..], |children| &**children);
+        for object in objects.iter_many(children) {
+            commands.entity(object).despawn_recursive();
+        }
+        commands.entity(entity).remove::<SpawnedObjects>();
     }
 }
@@ -0,0 +1,179 @@
+#![cfg(feature = "rapier")]
+
+use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+use bevy_rapier3d::rapier::parry::{
+    math::{Isometry, Point, Vector},
+    query::{Ray, RayCast},
+};
+use bevy_ymir::{
+    mesh::{self, ColliderMode, MeshConfig, RenderMode},
+    noise::{NoiseConfig, NoiseMap},
+    physics::{PhysicsBackend, RapierBackend},
+};
+
+const TOLERANCE: f32 = 1e-2;
+
+/// Height of the rendered triangle under `point`, found by barycentric interpolation.
+fn mesh_height(positions: &[[f32; 3]], indices: &[usize], point: Vec2) -> Option<f32> {
+    indices.chunks_exact(3).find_map(|triangle| {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| Vec3::from(positions[i]));
+        let (a2, b2, c2) = (
+            Vec2::new(a.x, a.z),
+            Vec2::new(b.x, b.z),
+            Vec2::new(c.x, c.z),
+        );
+
+        let det = (b2 - a2).perp_dot(c2 - a2);
+        let u = (point - a2).perp_dot(c2 - a2) / det;
+        let v = (b2 - a2).perp_dot(point - a2) / det;
+
+        let inside = u >= -1e-5 && v >= -1e-5 && u + v <= 1.0 + 1e-5;
+        inside.then(|| a.y + (b.y - a.y) * u + (c.y - a.y) * v)
+    })
+}
+
+fn assert_collider_matches_mesh(collider_mode: ColliderMode, grid_size: usize, scale: f32) {
+    let mesh_config = MeshConfig {
+        grid_size,
+        scale,
+        collider_mode,
+        flat_shading: false,
+        ..default()
+    };
+    let noise_config = NoiseConfig::default();
+    let map = NoiseMap::new(
+        &noise_config.fbm(),
+        grid_size,
+        (3, -2),
+        noise_config.offset,
+        false,
+    );
+    let data = mesh::get_mesh(&map, None, &mesh_config);
+
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        data.mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("chunk mesh has no positions");
+    };
+    let indices: Vec<usize> = data
+        .mesh
+        .indices()
+        .expect("chunk mesh has no indices")
+        .iter()
+        .collect();
+    let heights = data.heights;
+    let (collider, transform) =
+        RapierBackend::chunk_collider(&heights, &mesh_config).expect("chunk has no collider");
+
+    // NOTE rays are cast in collider space, so any collider transform is accounted for.
+    let to_world = transform.compute_matrix();
+    let to_local = to_world.inverse();
+    let step = scale / (grid_size - 1) as f32;
+    let top = mesh_config.height_multiplier * 10.0;
+
+    for y in 0..grid_size - 1 {
+        for x in 0..grid_size - 1 {
+            for (fx, fz) in [
+                (0.0, 0.0),
+                (0.25, 0.25),
+                (0.75, 0.25),
+                (0.25, 0.75),
+                (0.75, 0.75),
+            ] {
+                let corner = heights.position(x, y);
+                let point = Vec2::new(corner.x + fx * step, corner.z - fz * step);
+                let expected = mesh_height(positions, &indices, point)
+                    .unwrap_or_else(|| panic!("no mesh triangle under {point}"));
+
+                let origin = to_local.transform_point3(Vec3::new(point.x, top, point.y));
+                let dir = to_local.transform_vector3(Vec3::NEG_Y);
+                let ray = Ray::new(
+                    Point::new(origin.x, origin.y, origin.z),
+                    Vector::new(dir.x, dir.y, dir.z),
+                );
+                let toi = collider
+                    .raw
+                    .cast_ray(&Isometry::identity(), &ray, f32::MAX, true)
+                    .unwrap_or_else(|| panic!("ray at {point} missed the collider"));
+                let hit = to_world.transform_point3(origin + dir * toi);
+
+                assert!(
+                    (hit.y - expected).abs() < TOLERANCE,
+                    "collider height {} differs from mesh height {expected} at {point}",
+                    hit.y
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn heightfield_matches_default_mesh() {
+    let mesh_config = MeshConfig::default();
+    assert_collider_matches_mesh(
+        ColliderMode::Heightfield,
+        mesh_config.grid_size,
+        mesh_config.scale,
+    );
+}
+
+#[test]
+fn heightfield_matches_mesh_with_custom_grid_and_scale() {
+    assert_collider_matches_mesh(ColliderMode::Heightfield, 9, 37.5);
+    assert_collider_matches_mesh(ColliderMode::Heightfield, 65, 1000.0);
+}
+
+#[test]
+fn trimesh_matches_mesh() {
+    assert_collider_matches_mesh(ColliderMode::Trimesh, 9, 37.5);
+    assert_collider_matches_mesh(ColliderMode::Trimesh, 33, 256.0);
+}
+
+#[test]
+fn plane_gets_flat_cuboid() {
+    let mesh_config = MeshConfig {
+        render_mode: RenderMode::Plane,
+        ..default()
+    };
+    let map = NoiseMap::from_values(
+        mesh_config.grid_size,
+        vec![0.5; mesh_config.grid_size.pow(2)],
+    );
+    let heights = mesh::get_mesh(&map, None, &mesh_config).heights;
+    let (collider, transform) =
+        RapierBackend::chunk_collider(&heights, &mesh_config).expect("plane has no collider");
+
+    let to_world = transform.compute_matrix();
+    let to_local = to_world.inverse();
+    let half = mesh_config.scale / 2.0 - 1.0;
+    for (x, z) in [
+        (0.0, 0.0),
+        (-half, -half),
+        (half, -half),
+        (-half, half),
+        (half, half),
+    ] {
+        let origin = to_local.transform_point3(Vec3::new(x, 10.0, z));
+        let dir = to_local.transform_vector3(Vec3::NEG_Y);
+        let ray = Ray::new(
+            Point::new(origin.x, origin.y, origin.z),
+            Vector::new(dir.x, dir.y, dir.z),
+        );
+        let toi = collider
+            .raw
+            .cast_ray(&Isometry::identity(), &ray, f32::MAX, true)
+            .unwrap_or_else(|| panic!("ray at ({x}, {z}) missed the plane collider"));
+        let hit = to_world.transform_point3(origin + dir * toi);
+        assert!(
+            hit.y.abs() < TOLERANCE,
+            "plane collider top is at {}",
+            hit.y
+        );
+    }
+
+    let mesh_config = MeshConfig {
+        collider_mode: ColliderMode::None,
+        ..mesh_config
+    };
+    assert!(RapierBackend::chunk_collider(&heights, &mesh_config).is_none());
+}